@@ -0,0 +1,17 @@
+#!/bin/sh
+
+# Starts the broker, and creates the topics that require a specific configuration in the tests.
+
+echo "Kafka starting"
+rm -rf /var/lib/kafka
+kafka-server-start.sh /server.properties &
+kafka_pid=$!
+
+# Retry until the broker is registered, as topics can't be created before that.
+until kafka-topics.sh --zookeeper zookeeper:2181 --create --if-not-exists --topic __test_log_append_time \
+        --partitions 1 --replication-factor 1 --config message.timestamp.type=LogAppendTime > /dev/null 2>&1
+do
+    sleep 1
+done
+
+wait $kafka_pid
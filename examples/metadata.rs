@@ -43,7 +43,7 @@ fn print_metadata(brokers: &str, topic: Option<&str>, timeout_ms: i32, fetch_off
                      partition.isr(),
                      partition.error());
             if fetch_offsets {
-                let (low, high) = consumer.fetch_watermarks(topic.name(), partition.id(), 1000)
+                let (low, high) = consumer.fetch_watermarks(&topic.name(), partition.id(), 1000)
                     .unwrap_or((-1, -1));
                 println!("       Low watermark: {}  High watermark: {}", low, high);
             }
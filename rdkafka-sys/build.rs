@@ -1,5 +1,7 @@
 extern crate num_cpus;
 extern crate pkg_config;
+#[cfg(windows)]
+extern crate vcpkg;
 
 use std::path::Path;
 use std::process::{Command, self};
@@ -30,8 +32,17 @@ fn main() {
         .next()
         .expect("Crate version is not valid");
 
-    if env::var("CARGO_FEATURE_DYNAMIC_LINKING").is_ok() {
+    println!("cargo:rerun-if-env-changed=RDKAFKA_SYS_USE_PKG_CONFIG");
+    println!("cargo:rerun-if-env-changed=RDKAFKA_SYS_SKIP_SUBMODULE");
+    // The bundled sources are built with autotools, which are not available on Windows.
+    let unix_target = env::var("CARGO_CFG_TARGET_FAMILY").map(|family| family == "unix").unwrap_or(true);
+
+    if env::var("CARGO_FEATURE_DYNAMIC_LINKING").is_ok() || env::var("RDKAFKA_SYS_USE_PKG_CONFIG").is_ok()
+            || !unix_target {
         println_stderr!("Librdkafka will be linked dynamically");
+        if !unix_target && probe_vcpkg() {
+            return;
+        }
         let pkg_probe = pkg_config::Config::new()
             .cargo_metadata(true)
             .atleast_version(librdkafka_version)
@@ -56,6 +67,27 @@ fn main() {
     }
 }
 
+/// Looks for librdkafka in the vcpkg installation, emitting the linker configuration if found.
+#[cfg(windows)]
+fn probe_vcpkg() -> bool {
+    match vcpkg::find_package("librdkafka") {
+        Ok(library) => {
+            println_stderr!("librdkafka found with vcpkg:");
+            println_stderr!("  Path: {:?}", library.link_paths);
+            true
+        }
+        Err(e) => {
+            println_stderr!("librdkafka cannot be found with vcpkg: {}", e);
+            false
+        }
+    }
+}
+
+#[cfg(not(windows))]
+fn probe_vcpkg() -> bool {
+    false
+}
+
 fn build_librdkafka() {
     let mut configure_flags = Vec::new();
 
@@ -73,7 +105,14 @@ fn build_librdkafka() {
 
     configure_flags.push("--enable-static");
 
+    // The submodule is only fetched if the sources are missing: vendored sources are used as they
+    // are, without requiring git.
     if !Path::new("librdkafka/LICENSE").exists() {
+        if env::var("RDKAFKA_SYS_SKIP_SUBMODULE").is_ok() {
+            println_stderr!("The librdkafka sources are missing, and RDKAFKA_SYS_SKIP_SUBMODULE is set.");
+            println_stderr!("Copy the librdkafka sources in the rdkafka-sys/librdkafka directory, or unset the variable.");
+            process::exit(1);
+        }
         println_stderr!("Setting up submodules");
         run_command_or_fail("../", "git", &["submodule", "update", "--init"]);
     }
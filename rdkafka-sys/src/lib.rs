@@ -28,6 +28,16 @@
 //! to check the version of the library installed in the system, and it will configure the
 //! compiler to use dynamic linking.
 //!
+//! The same happens if the `RDKAFKA_SYS_USE_PKG_CONFIG` environment variable is set, which
+//! can be used to link a version of librdkafka installed with Homebrew on macOS. On Windows,
+//! where the bundled sources can't be built, the library is always looked up in the system:
+//! first with `vcpkg`, then with `pkg-config`.
+//!
+//! When building the bundled sources, the build script fetches the librdkafka git submodule if
+//! the sources are missing. Vendored sources are used as they are, without invoking git; set
+//! the `RDKAFKA_SYS_SKIP_SUBMODULE` environment variable to make the build fail instead of
+//! running git, for instance in hermetic build environments.
+//!
 //! The build process is defined in [`build.rs`].
 //!
 //! [`build.rs`]: https://github.com/fede1024/rust-rdkafka/blob/master/rdkafka-sys/build.rs
@@ -52,6 +52,12 @@ pub type RDKafkaGroupInfo = bindings::rd_kafka_group_info;
 /// Native rdkafka group member information
 pub type RDKafkaGroupMemberInfo = bindings::rd_kafka_group_member_info;
 
+/// Native rdkafka queue
+pub type RDKafkaQueue = bindings::rd_kafka_queue_t;
+
+/// Native rdkafka event
+pub type RDKafkaEvent = bindings::rd_kafka_event_t;
+
 // ENUMS
 
 /// Client types
@@ -281,6 +287,13 @@ impl From<RDKafkaRespErr> for RDKafkaError {
     }
 }
 
+impl From<RDKafkaError> for RDKafkaRespErr {
+    fn from(err: RDKafkaError) -> RDKafkaRespErr {
+        helpers::primitive_to_rd_kafka_resp_err_t(err as i32)
+            .expect("Every RDKafkaError has a corresponding RDKafkaRespErr")
+    }
+}
+
 impl fmt::Display for RDKafkaError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let description = match helpers::primitive_to_rd_kafka_resp_err_t(*self as i32) {
@@ -311,4 +324,14 @@ mod tests {
         assert_eq!("PartitionEOF (Broker: No more messages)", format!("{}", error));
         assert_eq!("PartitionEOF", format!("{:?}", error));
     }
+
+    #[test]
+    fn test_error_conversion() {
+        let raw = RDKafkaRespErr::RD_KAFKA_RESP_ERR_UNKNOWN_TOPIC_OR_PART;
+        let error: RDKafkaError = raw.into();
+        assert_eq!(error, RDKafkaError::UnknownTopicOrPartition);
+        assert_eq!(RDKafkaRespErr::from(error), raw);
+        assert_eq!(RDKafkaRespErr::from(RDKafkaError::OffsetOutOfRange),
+                   RDKafkaRespErr::RD_KAFKA_RESP_ERR_OFFSET_OUT_OF_RANGE);
+    }
 }
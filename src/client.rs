@@ -2,37 +2,42 @@
 use rdsys;
 use rdsys::types::*;
 
-use std::ffi::{CString, CStr};
+use std::collections::HashMap;
+use std::ffi::CString;
 use std::slice;
 use std::mem;
 use std::os::raw::c_void;
 use std::ptr;
+use std::sync::{Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
+use log::LogLevel;
 use serde_json;
 
-use config::{ClientConfig, NativeClientConfig, RDKafkaLogLevel};
-use error::{IsError, KafkaError, KafkaResult};
+use config::{ClientConfig, NativeClientConfig, RDKafkaLogLevel, TopicConfig};
+use error::{IsError, KafkaError, KafkaResult, RDKafkaError};
 use groups::GroupList;
 use metadata::Metadata;
-use statistics::Statistics;
-use util::bytes_cstr_to_owned;
+use statistics::{BrokerState, Statistics};
+use topic_partition_list::Offset;
+use util::{bytes_cstr_to_owned, cstr_to_owned, duration_to_millis, kafka_cstr};
 
 /// A Context is an object that can store user-defined data and on which callbacks can be
 /// defined. Refer to the list of methods to see which callbacks can currently be overridden.
 /// The context must be thread safe, and might be owned by multiple threads.
 pub trait Context: Send + Sync {
-    /// Receives log lines from librdkafka.
+    /// Receives log lines from librdkafka. The default implementation formats the line using
+    /// `format_log` and forwards it to the `log` crate using the `librdkafka` target, or to the
+    /// `tracing` crate if the `tracing` feature is enabled.
     fn log(&self, level: RDKafkaLogLevel, fac: &str, log_message: &str) {
-        match level {
-            RDKafkaLogLevel::Emerg => error!(target: "librdkafka", "librdkafka: {} {}", fac, log_message),
-            RDKafkaLogLevel::Alert => error!(target: "librdkafka", "librdkafka: {} {}", fac, log_message),
-            RDKafkaLogLevel::Critical => error!(target: "librdkafka", "librdkafka: {} {}", fac, log_message),
-            RDKafkaLogLevel::Error => error!(target: "librdkafka", "librdkafka: {} {}", fac, log_message),
-            RDKafkaLogLevel::Warning => warn!(target: "librdkafka", "librdkafka: {} {}", fac, log_message),
-            RDKafkaLogLevel::Notice => info!(target: "librdkafka", "librdkafka: {} {}", fac, log_message),
-            RDKafkaLogLevel::Info => info!(target: "librdkafka", "librdkafka: {} {}", fac, log_message),
-            RDKafkaLogLevel::Debug => debug!(target: "librdkafka", "librdkafka: {} {}", fac, log_message),
-        }
+        log_line(level, fac, &self.format_log(fac, log_message));
+    }
+
+    /// Formats the log lines received from librdkafka. This method can be overridden to change
+    /// the format of the log lines, while keeping the default level routing of `log`.
+    fn format_log(&self, fac: &str, log_message: &str) -> String {
+        format!("librdkafka: {} {}", fac, log_message)
     }
 
     /// Receives the statistics of the librdkafka client. To enable, the
@@ -41,9 +46,48 @@ pub trait Context: Send + Sync {
         info!("Client stats: {:?}", statistics);
     }
 
-    /// Receives global errors from the librdkafka client.
+    /// Receives the raw JSON of the statistics that couldn't be parsed into `Statistics`, for
+    /// example because a field has a different type in the librdkafka version in use. It can be
+    /// overridden to extract the needed values directly from the JSON. By default it does nothing:
+    /// the parsing error is logged in any case.
+    #[allow(unused_variables)]
+    fn stats_raw(&self, json: &str) {}
+
+    /// Receives the changes of state of the connection to a broker, such as a broker going down
+    /// or coming back up. The states are detected by comparing consecutive statistics, so this
+    /// method is only called if `statistics.interval.ms` is set, and transitions that happen
+    /// within the same statistics interval are not reported. Brokers are not reported when they
+    /// appear in the statistics for the first time. With the `EventConsumer`, this method is called
+    /// when the statistics event is polled.
+    fn broker_state_change(&self, broker_name: &str, broker_id: i32, old_state: BrokerState, new_state: BrokerState) {
+        info!("Broker {} (id {}) changed state from {:?} to {:?}", broker_name, broker_id, old_state, new_state);
+    }
+
+    /// Receives global errors from the librdkafka client. The default implementation logs the
+    /// error with the level returned by `error_log_level`.
     fn error(&self, error: KafkaError, reason: &str) {
-        error!("librdkafka: {}: {}", error, reason);
+        if let Some(level) = self.error_log_level(&error) {
+            log!(level, "librdkafka: {}: {}", error, reason);
+        }
+    }
+
+    /// Returns the log level that should be used by the default `error` implementation to log the
+    /// specified error, or `None` if the error shouldn't be logged. This method can be overridden
+    /// to silence or downgrade specific errors, such as `RDKafkaError::AllBrokersDown` during
+    /// startup. By default all errors are logged with level `Error`.
+    #[allow(unused_variables)]
+    fn error_log_level(&self, error: &KafkaError) -> Option<LogLevel> {
+        Some(LogLevel::Error)
+    }
+
+    /// Receives the throttle time reported by a broker in a produce or fetch response. This is
+    /// called whenever a broker applies a quota to the client, and can be used to detect and react
+    /// to quota enforcement. Aggregated throttle statistics per broker are also available in the
+    /// `throttle` field of the broker statistics.
+    fn throttle(&self, broker_name: &str, broker_id: i32, throttle_time_ms: i32) {
+        if throttle_time_ms > 0 {
+            warn!("Throttled by broker {} (id {}) for {}ms", broker_name, broker_id, throttle_time_ms);
+        }
     }
 
     // NOTE: when adding a new method, remember to add it to the FutureProducerContext as well.
@@ -62,6 +106,38 @@ impl EmptyContext {
     }
 }
 
+/// Forwards a log line to the `log` crate, mapping the librdkafka log level to the corresponding
+/// log crate level.
+#[cfg(not(feature = "tracing"))]
+fn log_line(level: RDKafkaLogLevel, _fac: &str, line: &str) {
+    match level {
+        RDKafkaLogLevel::Emerg => error!(target: "librdkafka", "{}", line),
+        RDKafkaLogLevel::Alert => error!(target: "librdkafka", "{}", line),
+        RDKafkaLogLevel::Critical => error!(target: "librdkafka", "{}", line),
+        RDKafkaLogLevel::Error => error!(target: "librdkafka", "{}", line),
+        RDKafkaLogLevel::Warning => warn!(target: "librdkafka", "{}", line),
+        RDKafkaLogLevel::Notice => info!(target: "librdkafka", "{}", line),
+        RDKafkaLogLevel::Info => info!(target: "librdkafka", "{}", line),
+        RDKafkaLogLevel::Debug => debug!(target: "librdkafka", "{}", line),
+    }
+}
+
+/// Forwards a log line to the `tracing` crate as an event, with the librdkafka facility stored in
+/// the `fac` field.
+#[cfg(feature = "tracing")]
+fn log_line(level: RDKafkaLogLevel, fac: &str, line: &str) {
+    match level {
+        RDKafkaLogLevel::Emerg => tracing::error!(target: "librdkafka", fac = fac, "{}", line),
+        RDKafkaLogLevel::Alert => tracing::error!(target: "librdkafka", fac = fac, "{}", line),
+        RDKafkaLogLevel::Critical => tracing::error!(target: "librdkafka", fac = fac, "{}", line),
+        RDKafkaLogLevel::Error => tracing::error!(target: "librdkafka", fac = fac, "{}", line),
+        RDKafkaLogLevel::Warning => tracing::warn!(target: "librdkafka", fac = fac, "{}", line),
+        RDKafkaLogLevel::Notice => tracing::info!(target: "librdkafka", fac = fac, "{}", line),
+        RDKafkaLogLevel::Info => tracing::info!(target: "librdkafka", fac = fac, "{}", line),
+        RDKafkaLogLevel::Debug => tracing::debug!(target: "librdkafka", fac = fac, "{}", line),
+    }
+}
+
 //
 // ********** CLIENT **********
 //
@@ -98,12 +174,148 @@ impl Drop for NativeClient {
     }
 }
 
+/// The object registered as opaque pointer of a librdkafka client, and passed by librdkafka to the
+/// callbacks. It contains the context, together with the state that the callbacks keep for the
+/// client. It's owned by the `Client`, and it's destroyed together with it. This struct shouldn't
+/// be used directly.
+pub struct OpaqueContext<C: Context> {
+    context: C,
+    // Broker states seen in the last statistics, indexed by broker name.
+    broker_states: Mutex<HashMap<String, BrokerState>>,
+    // Starting offsets of the partitions not assigned yet, only used by consumers.
+    start_offsets: Mutex<HashMap<(String, i32), Offset>>,
+    // Number of assignments served by the rebalance callback, only used by consumers.
+    assignment_count: Mutex<u64>,
+    assignment_served: Condvar,
+    // Whether the rebalance callback should pause the partitions it assigns.
+    pause_assignments: AtomicBool,
+}
+
+impl<C: Context> OpaqueContext<C> {
+    fn new(context: C) -> OpaqueContext<C> {
+        OpaqueContext {
+            context,
+            broker_states: Mutex::new(HashMap::new()),
+            start_offsets: Mutex::new(HashMap::new()),
+            assignment_count: Mutex::new(0),
+            assignment_served: Condvar::new(),
+            pause_assignments: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the `OpaqueContext` registered as opaque pointer of a client. The pointer must have
+    /// been received from librdkafka, and the returned reference must not outlive the client.
+    pub unsafe fn from_opaque<'a>(opaque: *mut c_void) -> &'a OpaqueContext<C> {
+        &*(opaque as *const OpaqueContext<C>)
+    }
+
+    /// Returns a reference to the context.
+    pub fn context(&self) -> &C {
+        &self.context
+    }
+
+    /// Returns the starting offsets requested with `Consumer::subscribe_with_offsets` for the
+    /// partitions that haven't been assigned yet, indexed by topic and partition.
+    pub fn start_offsets(&self) -> &Mutex<HashMap<(String, i32), Offset>> {
+        &self.start_offsets
+    }
+
+    /// Returns the number of assignments served by the rebalance callback of the consumer.
+    pub fn assignment_count(&self) -> u64 {
+        *self.assignment_count.lock().unwrap()
+    }
+
+    /// Records that the rebalance callback has served a new assignment, and wakes up the threads
+    /// waiting for it.
+    pub fn notify_assignment(&self) {
+        *self.assignment_count.lock().unwrap() += 1;
+        self.assignment_served.notify_all();
+    }
+
+    /// Returns true if the rebalance callback should pause the partitions it assigns.
+    pub fn pause_assignments(&self) -> bool {
+        self.pause_assignments.load(Ordering::SeqCst)
+    }
+
+    /// Sets whether the rebalance callback should pause the partitions it assigns.
+    pub fn set_pause_assignments(&self, pause: bool) {
+        self.pause_assignments.store(pause, Ordering::SeqCst);
+    }
+
+    /// Waits until the number of assignments served by the rebalance callback exceeds `count`.
+    /// Returns `false` if the timeout expires first; a `None` timeout waits indefinitely.
+    pub fn wait_for_assignment_after(&self, count: u64, timeout: Option<Duration>) -> bool {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut assignment_count = self.assignment_count.lock().unwrap();
+        while *assignment_count <= count {
+            match deadline {
+                None => assignment_count = self.assignment_served.wait(assignment_count).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return false;
+                    }
+                    assignment_count = self.assignment_served.wait_timeout(assignment_count, deadline - now).unwrap().0;
+                }
+            }
+        }
+        true
+    }
+
+    /// Compares the broker states in the statistics with the ones seen in the previous statistics,
+    /// and notifies the context of every change.
+    pub fn notify_broker_state_changes(&self, stats: &Statistics) {
+        let mut changes = Vec::new();
+        {
+            let mut states = self.broker_states.lock().unwrap();
+            for (name, broker) in &stats.brokers {
+                let new_state = broker.broker_state();
+                if let Some(old_state) = states.insert(name.clone(), new_state) {
+                    if old_state != new_state {
+                        changes.push((name.as_str(), broker.nodeid, old_state, new_state));
+                    }
+                }
+            }
+        }
+        // The context is called without holding the lock.
+        for (name, broker_id, old_state, new_state) in changes {
+            self.context.broker_state_change(name, broker_id, old_state, new_state);
+        }
+    }
+}
+
 /// A low level rdkafka client. This client shouldn't be used directly. The producer and consumer modules
 /// provide different producer and consumer implementations based on top of `Client` that can be
 /// used instead.
 pub struct Client<C: Context> {
+    // Fields are dropped in declaration order: the log queue must be destroyed before the native
+    // client, and the native client must be destroyed before the context, since librdkafka might
+    // still call the callbacks while the client is being destroyed.
+    log_queue: Option<NativeQueue>,
     native: NativeClient,
-    context: Box<C>,
+    context: Box<OpaqueContext<C>>,
+    leader_cache: Mutex<LeaderCache>,
+}
+
+/// Partition leaders returned by `Client::partition_leader`, cached per topic.
+struct LeaderCache {
+    ttl: Duration,
+    topics: HashMap<String, (Instant, HashMap<i32, i32>)>,
+}
+
+impl LeaderCache {
+    fn new() -> LeaderCache {
+        LeaderCache { ttl: Duration::from_secs(60), topics: HashMap::new() }
+    }
+}
+
+/// librdkafka reports -1 as leader of partitions without a leader.
+fn valid_leader(leader: i32) -> Option<i32> {
+    if leader >= 0 {
+        Some(leader)
+    } else {
+        None
+    }
 }
 
 impl<C: Context> Client<C> {
@@ -112,30 +324,71 @@ impl<C: Context> Client<C> {
                context: C)
             -> KafkaResult<Client<C>> {
         let errstr = [0i8; 1024];
-        let mut boxed_context = Box::new(context);
-        unsafe { rdsys::rd_kafka_conf_set_opaque(native_config.ptr(), (&mut *boxed_context) as *mut C as *mut c_void) };
+        let mut boxed_context = Box::new(OpaqueContext::new(context));
+        unsafe {
+            rdsys::rd_kafka_conf_set_opaque(native_config.ptr(), (&mut *boxed_context) as *mut OpaqueContext<C> as *mut c_void)
+        };
         unsafe { rdsys::rd_kafka_conf_set_log_cb(native_config.ptr(), Some(native_log_cb::<C>)) };
         unsafe { rdsys::rd_kafka_conf_set_stats_cb(native_config.ptr(), Some(native_stats_cb::<C>)) };
         unsafe { rdsys::rd_kafka_conf_set_error_cb(native_config.ptr(), Some(native_error_cb::<C>)) };
+        unsafe { rdsys::rd_kafka_conf_set_throttle_cb(native_config.ptr(), Some(native_throttle_cb::<C>)) };
 
         let client_ptr = unsafe {
-            rdsys::rd_kafka_new(rd_kafka_type, native_config.ptr_move(), errstr.as_ptr() as *mut i8, errstr.len())
+            rdsys::rd_kafka_new(rd_kafka_type, native_config.ptr(), errstr.as_ptr() as *mut i8, errstr.len())
         };
         trace!("Create new librdkafka client {:p}", client_ptr);
 
         if client_ptr.is_null() {
+            // If the creation fails, the configuration is still owned by the caller and it will
+            // be freed when native_config is dropped.
             let descr = unsafe { bytes_cstr_to_owned(&errstr) };
             return Err(KafkaError::ClientCreation(descr));
         }
+        // The configuration is now owned by the client.
+        mem::forget(native_config);
+        let native = NativeClient::from_ptr(client_ptr);
 
         unsafe { rdsys::rd_kafka_set_log_level(client_ptr, config.log_level as i32) };
 
+        let log_queue = if config.get("log.queue") == Some("true") {
+            let queue = NativeQueue::from_ptr(unsafe { rdsys::rd_kafka_queue_new(client_ptr) });
+            let ret = unsafe { rdsys::rd_kafka_set_log_queue(client_ptr, queue.ptr()) };
+            if ret.is_error() {
+                return Err(KafkaError::ClientCreation(format!("Failed to set log queue: {:?}", ret)));
+            }
+            Some(queue)
+        } else {
+            None
+        };
+
         Ok(Client {
-            native: NativeClient::from_ptr(client_ptr),
+            log_queue: log_queue,
+            native: native,
             context: boxed_context,
+            leader_cache: Mutex::new(LeaderCache::new()),
         })
     }
 
+    /// Creates a new `Client` wrapping an existing librdkafka client handle, for instance one
+    /// created by C code sharing the same librdkafka instance.
+    ///
+    /// The returned `Client` takes ownership of the handle, and will call `rd_kafka_destroy` on it
+    /// when dropped: the caller must not destroy the handle itself, nor use it after the `Client`
+    /// has been dropped. Since the handle has been configured outside of rust-rdkafka, the
+    /// callbacks of the context will not be invoked by librdkafka, and the context is only stored
+    /// to be returned by `context`.
+    ///
+    /// # Safety
+    /// The pointer must be a valid, non null, librdkafka client handle.
+    pub unsafe fn from_raw(ptr: *mut RDKafka, context: C) -> Client<C> {
+        Client {
+            log_queue: None,
+            native: NativeClient::from_ptr(ptr),
+            context: Box::new(OpaqueContext::new(context)),
+            leader_cache: Mutex::new(LeaderCache::new()),
+        }
+    }
+
     /// Returns a reference to the native rdkafka-sys client.
     pub fn native_client(&self) -> &NativeClient {
         &self.native
@@ -148,7 +401,89 @@ impl<C: Context> Client<C> {
 
     /// Returns a reference to the context.
     pub fn context(&self) -> &C {
-        self.context.as_ref()
+        self.context.context()
+    }
+
+    /// Returns a reference to the object registered as opaque pointer of the native client.
+    pub fn opaque_context(&self) -> &OpaqueContext<C> {
+        &self.context
+    }
+
+    /// Polls the client for events, serving the queued callbacks (such as delivery reports, errors
+    /// and statistics) in the calling thread. It won't block for more than `timeout_ms`
+    /// milliseconds. Returns the number of events served.
+    ///
+    /// This method should not be used with consumers: when a consumer is created, all its events
+    /// are redirected to the consumer queue, and they are served by `BaseConsumer::poll` instead.
+    pub fn poll(&self, timeout_ms: i32) -> i32 {
+        unsafe { rdsys::rd_kafka_poll(self.native_ptr(), timeout_ms) }
+    }
+
+    /// Serves the log events queued by librdkafka, dispatching them to `Context::log`. Log events
+    /// are only queued if the client was created with `log.queue` set to `true`: in this case the
+    /// log lines are delivered to the thread calling this method, instead of being logged directly
+    /// from the internal librdkafka threads. The call blocks up to `timeout_ms` waiting for the
+    /// first event, and returns the number of events served. If the log queue is not enabled, it
+    /// returns 0 immediately.
+    pub fn poll_logs(&self, timeout_ms: i32) -> usize {
+        let queue = match self.log_queue {
+            Some(ref queue) => queue,
+            None => return 0,
+        };
+        let mut count = 0;
+        let mut timeout_ms = timeout_ms;
+        loop {
+            let event = unsafe { rdsys::rd_kafka_queue_poll(queue.ptr(), timeout_ms) };
+            if event.is_null() {
+                break;
+            }
+            if unsafe { rdsys::rd_kafka_event_type(event) } == rdsys::RD_KAFKA_EVENT_LOG as i32 {
+                let mut fac = ptr::null();
+                let mut buf = ptr::null();
+                let mut level = 0;
+                let ret = unsafe { rdsys::rd_kafka_event_log(event, &mut fac, &mut buf, &mut level) };
+                if ret == 0 {
+                    let fac = unsafe { kafka_cstr(fac) };
+                    let log_message = unsafe { kafka_cstr(buf) };
+                    self.context().log(RDKafkaLogLevel::from_int(level), fac.trim(), log_message.trim());
+                }
+            }
+            unsafe { rdsys::rd_kafka_event_destroy(event) };
+            count += 1;
+            // Only wait for the first event.
+            timeout_ms = 0;
+        }
+        count
+    }
+
+    /// Returns the name of the client, as assigned by librdkafka (e.g. `rdkafka#producer-1`).
+    /// The same name is used by librdkafka in its log lines.
+    pub fn name(&self) -> String {
+        unsafe { cstr_to_owned(rdsys::rd_kafka_name(self.native_ptr())) }
+    }
+
+    /// Returns the id of the cluster the client is connected to, waiting up to `timeout_ms` for
+    /// the initial metadata to be received. Set the timeout to -1 to wait indefinitely. If no
+    /// metadata is received within the timeout, a `KafkaError::MetadataFetch` error containing
+    /// `RDKafkaError::OperationTimedOut` is returned: with a timeout of 0, this is always the case
+    /// unless the cluster id is already known. `None` is returned if the metadata has been received
+    /// but it doesn't contain the cluster id, which is only reported by Kafka 0.10.1 or later when
+    /// `api.version.request` is set to `true`.
+    pub fn cluster_id(&self, timeout_ms: i32) -> KafkaResult<Option<String>> {
+        let start_time = Instant::now();
+        let cluster_id_ptr = unsafe { rdsys::rd_kafka_clusterid(self.native_ptr(), timeout_ms) };
+        if cluster_id_ptr.is_null() {
+            // librdkafka returns before the timeout expires only if the metadata has been received
+            // without a cluster id.
+            let elapsed_ms = duration_to_millis(start_time.elapsed());
+            if timeout_ms >= 0 && elapsed_ms >= timeout_ms as u64 {
+                return Err(KafkaError::MetadataFetch(RDKafkaError::OperationTimedOut));
+            }
+            return Ok(None);
+        }
+        let cluster_id = unsafe { cstr_to_owned(cluster_id_ptr) };
+        unsafe { rdsys::rd_kafka_mem_free(self.native_ptr(), cluster_id_ptr as *mut c_void) };
+        Ok(Some(cluster_id))
     }
 
     /// Returns the metadata information for the specified topic, or for all topics in the cluster
@@ -177,6 +512,91 @@ impl<C: Context> Client<C> {
         Ok(Metadata::from_ptr(metadata_ptr))
     }
 
+    /// Returns the id of the broker leading the given partition, or `None` if the partition
+    /// doesn't exist or has no leader. The leaders are cached per topic, and the metadata of the
+    /// topic is only fetched if it's not in the cache or if it's older than the cache TTL (60
+    /// seconds by default, see `set_partition_leader_ttl`). If the metadata fetch fails, the
+    /// previously cached value is returned, if any.
+    pub fn partition_leader(&self, topic: &str, partition: i32, timeout_ms: i32) -> Option<i32> {
+        {
+            let cache = self.leader_cache.lock().unwrap();
+            if let Some(&(fetch_time, ref leaders)) = cache.topics.get(topic) {
+                if fetch_time.elapsed() < cache.ttl {
+                    return leaders.get(&partition).cloned().and_then(valid_leader);
+                }
+            }
+        }
+        // The lock is not held during the fetch, so that other topics can still be looked up.
+        match self.fetch_metadata(Some(topic), timeout_ms) {
+            Ok(metadata) => {
+                let leaders = metadata.topics().iter()
+                    .filter(|topic_metadata| topic_metadata.name() == topic)
+                    .flat_map(|topic_metadata| topic_metadata.partitions().iter())
+                    .map(|partition_metadata| (partition_metadata.id(), partition_metadata.leader()))
+                    .collect::<HashMap<_, _>>();
+                let leader = leaders.get(&partition).cloned().and_then(valid_leader);
+                let mut cache = self.leader_cache.lock().unwrap();
+                cache.topics.insert(topic.to_owned(), (Instant::now(), leaders));
+                leader
+            },
+            Err(e) => {
+                warn!("Failed to fetch metadata for topic {}: {}", topic, e);
+                let cache = self.leader_cache.lock().unwrap();
+                let cached_leader = cache.topics.get(topic)
+                    .and_then(|&(_, ref leaders)| leaders.get(&partition).cloned());
+                cached_leader.and_then(valid_leader)
+            }
+        }
+    }
+
+    /// Sets how long the partition leaders returned by `partition_leader` are cached. Setting the
+    /// TTL doesn't invalidate the leaders already in cache, unless they are older than the new TTL.
+    pub fn set_partition_leader_ttl(&self, ttl: Duration) {
+        self.leader_cache.lock().unwrap().ttl = ttl;
+    }
+
+    /// Returns true if the topic exists in the cluster, false otherwise. An error is returned only
+    /// if the metadata couldn't be fetched. Note that, if automatic topic creation is enabled in
+    /// the broker, the metadata request might cause the creation of the topic.
+    pub fn topic_exists(&self, topic: &str, timeout_ms: i32) -> KafkaResult<bool> {
+        let metadata = self.fetch_metadata(Some(topic), timeout_ms)?;
+        let exists = metadata.topics().iter()
+            .filter(|topic_metadata| topic_metadata.name() == topic)
+            .any(|topic_metadata| match topic_metadata.error() {
+                Some(RDKafkaRespErr::RD_KAFKA_RESP_ERR_UNKNOWN_TOPIC_OR_PART) |
+                Some(RDKafkaRespErr::RD_KAFKA_RESP_ERR__UNKNOWN_TOPIC) => false,
+                _ => true,
+            });
+        Ok(exists)
+    }
+
+    /// Returns the metadata information for the specified topics, without fetching the metadata
+    /// of the whole cluster. The returned metadata contains the requested topics, and it might
+    /// also contain other topics already known to the client (for example, subscribed topics).
+    pub fn fetch_metadata_for_topics(&self, topics: &[&str], timeout_ms: i32) -> KafkaResult<Metadata> {
+        // The topic handles must be alive during the fetch, so that librdkafka will consider
+        // the topics as locally known.
+        let native_topics = topics.iter()
+            .map(|topic| self.native_topic(topic))
+            .collect::<KafkaResult<Vec<_>>>()?;
+        let mut metadata_ptr: *const RDKafkaMetadata = ptr::null_mut();
+        trace!("Starting metadata fetch for {} topics", native_topics.len());
+        let ret = unsafe {
+            rdsys::rd_kafka_metadata(
+                self.native_ptr(),
+                0,
+                NativeTopic::null(),
+                &mut metadata_ptr as *mut *const RDKafkaMetadata,
+                timeout_ms)
+        };
+        trace!("Metadata fetch completed");
+        if ret.is_error() {
+            return Err(KafkaError::MetadataFetch(ret.into()));
+        }
+
+        Ok(Metadata::from_ptr(metadata_ptr))
+    }
+
     /// Returns high and low watermark for the specified topic and partition.
     pub fn fetch_watermarks(&self, topic: &str, partition: i32, timeout_ms: i32) -> KafkaResult<(i64, i64)> {
         let mut low = -1;
@@ -221,15 +641,65 @@ impl<C: Context> Client<C> {
 
     /// Returns a NativeTopic from the current client. The NativeTopic shouldn't outlive the client
     /// it was generated from.
-    fn native_topic(&self, topic: &str) -> KafkaResult<NativeTopic> {
+    pub fn native_topic(&self, topic: &str) -> KafkaResult<NativeTopic> {
         let topic_c = CString::new(topic.to_string())?;
         let native_topic_ptr = unsafe {
             rdsys::rd_kafka_topic_new(self.native_ptr(), topic_c.as_ptr(), ptr::null_mut()) };
+        if native_topic_ptr.is_null() {
+            return Err(KafkaError::ClientCreation(format!("Failed to create topic handle for {}", topic)));
+        }
         Ok(NativeTopic::from_ptr(native_topic_ptr))
     }
+
+    /// Returns a NativeTopic from the current client, configured with the provided topic
+    /// configuration. The configuration is only applied if no other handle for the same topic
+    /// exists. The NativeTopic shouldn't outlive the client it was generated from.
+    pub fn native_topic_with_config(&self, topic: &str, config: &TopicConfig) -> KafkaResult<NativeTopic> {
+        let topic_c = CString::new(topic.to_string())?;
+        let native_config = config.create_native_config()?;
+        let native_topic_ptr = unsafe {
+            rdsys::rd_kafka_topic_new(self.native_ptr(), topic_c.as_ptr(), native_config.ptr_move()) };
+        if native_topic_ptr.is_null() {
+            return Err(KafkaError::ClientCreation(format!("Failed to create topic handle for {}", topic)));
+        }
+        Ok(NativeTopic::from_ptr(native_topic_ptr))
+    }
+}
+
+/// A native rdkafka-sys queue. This struct shouldn't be used directly, and shouldn't outlive the
+/// client it was created from.
+pub struct NativeQueue {
+    ptr: *mut RDKafkaQueue,
+}
+
+unsafe impl Send for NativeQueue {}
+unsafe impl Sync for NativeQueue {}
+
+impl NativeQueue {
+    /// Wraps a pointer to an `RDKafkaQueue` object and returns a new `NativeQueue`.
+    pub fn from_ptr(ptr: *mut RDKafkaQueue) -> NativeQueue {
+        NativeQueue { ptr: ptr }
+    }
+
+    /// Returns the pointer to the librdkafka RDKafkaQueue structure.
+    pub fn ptr(&self) -> *mut RDKafkaQueue {
+        self.ptr
+    }
+}
+
+impl Drop for NativeQueue {
+    fn drop(&mut self) {
+        trace!("Destroying NativeQueue: {:?}", self.ptr);
+        unsafe {
+            rdsys::rd_kafka_queue_destroy(self.ptr);
+        }
+        trace!("NativeQueue destroyed: {:?}", self.ptr);
+    }
 }
 
-struct NativeTopic {
+/// A native rdkafka-sys topic handle. This struct shouldn't be used directly, and shouldn't
+/// outlive the client it was created from.
+pub struct NativeTopic {
     ptr: *mut RDKafkaTopic,
 }
 
@@ -237,13 +707,17 @@ unsafe impl Send for NativeTopic {}
 unsafe impl Sync for NativeTopic {}
 
 impl NativeTopic {
-    /// Wraps a pointer to an `RDKafkaTopic` object and returns a new `Native `.
-    fn from_ptr(ptr: *mut RDKafkaTopic) -> NativeTopic {
+    /// Wraps a pointer to an `RDKafkaTopic` object and returns a new `NativeTopic`. The
+    /// `NativeTopic` takes ownership of the handle and will call `rd_kafka_topic_destroy` on it
+    /// when dropped. Since librdkafka topic handles are reference counted, code sharing a topic
+    /// with rust-rdkafka should obtain its own handle using `rd_kafka_topic_new`, and destroy it
+    /// independently.
+    pub fn from_ptr(ptr: *mut RDKafkaTopic) -> NativeTopic {
         NativeTopic { ptr: ptr }
     }
 
     /// Returns the pointer to the librdkafka RDKafkaTopic structure.
-    fn ptr(&self) -> *mut RDKafkaTopic {
+    pub fn ptr(&self) -> *mut RDKafkaTopic {
         self.ptr
     }
 
@@ -266,32 +740,36 @@ impl Drop for NativeTopic {
 pub unsafe extern "C" fn native_log_cb<C: Context>(
         client: *const RDKafka, level: i32,
         fac: *const i8, buf: *const i8) {
-    let fac = CStr::from_ptr(fac).to_string_lossy();
-    let log_message = CStr::from_ptr(buf).to_string_lossy();
+    let fac = kafka_cstr(fac);
+    let log_message = kafka_cstr(buf);
 
-    let context = Box::from_raw(rdsys::rd_kafka_opaque(client) as *mut C);
-    (*context).log(RDKafkaLogLevel::from_int(level), fac.trim(), log_message.trim());
-    mem::forget(context);   // Do not free the context
+    let context = OpaqueContext::<C>::from_opaque(rdsys::rd_kafka_opaque(client)).context();
+    context.log(RDKafkaLogLevel::from_int(level), fac.trim(), log_message.trim());
 }
 
 pub unsafe extern "C" fn native_stats_cb<C: Context>(
-        _conf: *mut RDKafka, json: *mut i8, json_len: usize,
+        _client: *mut RDKafka, json: *mut i8, json_len: usize,
         opaque: *mut c_void) -> i32 {
-    let context = Box::from_raw(opaque as *mut C);
+    let opaque_context = OpaqueContext::<C>::from_opaque(opaque);
+    let context = opaque_context.context();
 
     let mut bytes_vec = Vec::new();
     bytes_vec.extend_from_slice(slice::from_raw_parts(json as *mut u8, json_len));
     let json_string = CString::from_vec_unchecked(bytes_vec).into_string();
     match json_string {
         Ok(json) => match serde_json::from_str(&json) {
-            Ok(stats) => (*context).stats(stats),
-            Err(e) => error!("Could not parse statistics JSON: {}", e)
+            Ok(stats) => {
+                opaque_context.notify_broker_state_changes(&stats);
+                context.stats(stats)
+            },
+            Err(e) => {
+                error!("Could not parse statistics JSON: {}", e);
+                context.stats_raw(&json)
+            }
         },
         Err(e) => error!("Statistics JSON string is not UTF-8: {:?}", e)
     }
 
-    mem::forget(context);   // Do not free the context
-
     0 // librdkafka will free the json buffer
 }
 
@@ -301,11 +779,19 @@ pub unsafe extern "C" fn native_error_cb<C: Context>(
     let err = rdsys::primitive_to_rd_kafka_resp_err_t(err)
         .expect("global error not an rd_kafka_resp_err_t");
     let error = KafkaError::Global(err.into());
-    let reason = CStr::from_ptr(reason).to_string_lossy();
+    let reason = kafka_cstr(reason);
 
-    let context = Box::from_raw(opaque as *mut C);
-    (*context).error(error, reason.trim());
-    mem::forget(context);   // Do not free the context
+    let context = OpaqueContext::<C>::from_opaque(opaque).context();
+    context.error(error, reason.trim());
+}
+
+pub unsafe extern "C" fn native_throttle_cb<C: Context>(
+        _client: *mut RDKafka, broker_name: *const i8, broker_id: i32,
+        throttle_time_ms: i32, opaque: *mut c_void) {
+    let broker_name = kafka_cstr(broker_name);
+
+    let context = OpaqueContext::<C>::from_opaque(opaque).context();
+    context.throttle(&broker_name, broker_id, throttle_time_ms);
 }
 
 #[cfg(test)]
@@ -324,5 +810,94 @@ mod tests {
         let client = Client::new(&config, native_config, RDKafkaType::RD_KAFKA_PRODUCER,
                                  EmptyContext::new()).unwrap();
         assert!(!client.native_ptr().is_null());
+        assert!(client.name().starts_with("rdkafka#producer-"));
+    }
+
+    #[test]
+    fn test_client_log_queue() {
+        let mut config = ClientConfig::new();
+        config.set("log.queue", "true");
+        let native_config = config.create_native_config().unwrap();
+        let client = Client::new(&config, native_config, RDKafkaType::RD_KAFKA_PRODUCER,
+                                 EmptyContext::new()).unwrap();
+        assert!(client.log_queue.is_some());
+        client.poll_logs(0);
+    }
+
+    #[test]
+    fn test_client_duplicate_config() {
+        let config = ClientConfig::new();
+        let native_config = config.create_native_config().unwrap();
+        let native_config_copy = native_config.duplicate();
+        let client_1 = Client::new(&config, native_config, RDKafkaType::RD_KAFKA_PRODUCER,
+                                   EmptyContext::new()).unwrap();
+        let client_2 = Client::new(&config, native_config_copy, RDKafkaType::RD_KAFKA_PRODUCER,
+                                   EmptyContext::new()).unwrap();
+        assert_ne!(client_1.name(), client_2.name());
+    }
+
+    #[test]
+    fn test_client_from_raw() {
+        let config = ClientConfig::new();
+        let native_config = config.create_native_config().unwrap();
+        let errstr = [0i8; 1024];
+        let client_ptr = unsafe {
+            rdsys::rd_kafka_new(RDKafkaType::RD_KAFKA_CONSUMER, native_config.ptr_move(),
+                                errstr.as_ptr() as *mut i8, errstr.len())
+        };
+        assert!(!client_ptr.is_null());
+        let client = unsafe { Client::from_raw(client_ptr, EmptyContext::new()) };
+        assert_eq!(client.native_ptr(), client_ptr);
+        assert!(client.name().starts_with("rdkafka#consumer-"));
+    }
+
+    // Without a cluster, the metadata can't be fetched and no leader is returned.
+    #[test]
+    fn test_client_partition_leader_unavailable() {
+        let config = ClientConfig::new();
+        let native_config = config.create_native_config().unwrap();
+        let client = Client::new(&config, native_config, RDKafkaType::RD_KAFKA_PRODUCER, EmptyContext::new()).unwrap();
+        client.set_partition_leader_ttl(Duration::from_secs(1));
+        assert_eq!(client.partition_leader("topic", 0, 100), None);
+    }
+
+    struct BrokerStateContext {
+        changes: Mutex<Vec<(String, BrokerState, BrokerState)>>,
+    }
+
+    impl Context for BrokerStateContext {
+        fn broker_state_change(&self, broker_name: &str, _: i32, old_state: BrokerState, new_state: BrokerState) {
+            self.changes.lock().unwrap().push((broker_name.to_owned(), old_state, new_state));
+        }
+    }
+
+    fn stats_with_broker_state(state: &str) -> Statistics {
+        let mut stats = Statistics::default();
+        let mut broker = ::statistics::Broker::default();
+        broker.name = "localhost:9092/0".to_owned();
+        broker.state = state.to_owned();
+        stats.brokers.insert(broker.name.clone(), broker);
+        stats
+    }
+
+    // Brokers seen for the first time are not reported, and the following changes are.
+    #[test]
+    fn test_client_broker_state_change() {
+        let opaque_context = OpaqueContext::new(BrokerStateContext { changes: Mutex::new(Vec::new()) });
+        opaque_context.notify_broker_state_changes(&stats_with_broker_state("UP"));
+        opaque_context.notify_broker_state_changes(&stats_with_broker_state("UP"));
+        assert!(opaque_context.context().changes.lock().unwrap().is_empty());
+        opaque_context.notify_broker_state_changes(&stats_with_broker_state("DOWN"));
+        assert_eq!(*opaque_context.context().changes.lock().unwrap(),
+                   vec![("localhost:9092/0".to_owned(), BrokerState::Up, BrokerState::Down)]);
+    }
+
+    // Without a cluster, no metadata is received and the request times out.
+    #[test]
+    fn test_client_cluster_id_unavailable() {
+        let config = ClientConfig::new();
+        let native_config = config.create_native_config().unwrap();
+        let client = Client::new(&config, native_config, RDKafkaType::RD_KAFKA_PRODUCER, EmptyContext::new()).unwrap();
+        assert_eq!(client.cluster_id(100), Err(KafkaError::MetadataFetch(RDKafkaError::OperationTimedOut)));
     }
 }
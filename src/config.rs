@@ -24,14 +24,19 @@ use rdsys;
 
 use client::Context;
 use error::{KafkaError, KafkaResult, IsError};
-use util::bytes_cstr_to_owned;
+use util::{bytes_cstr_to_owned, duration_to_millis};
 
 use std::collections::HashMap;
 use std::ffi::CString;
+use std::fs::File;
 use std::mem;
+use std::time::Duration;
 
 const ERR_LEN: usize = 256;
 
+/// Range of values accepted by librdkafka for `socket.timeout.ms`.
+const SOCKET_TIMEOUT_RANGE_MS: (u64, u64) = (10, 300_000);
+
 
 /// The log levels supported by librdkafka.
 #[derive(Copy, Clone, Debug)]
@@ -69,6 +74,127 @@ impl RDKafkaLogLevel {
     }
 }
 
+/// The number of acknowledgements the producer requires from the brokers before considering a
+/// request complete.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Acks {
+    /// The broker doesn't send any response: messages might be lost without notice.
+    None,
+    /// Only the leader acknowledges the write: messages might be lost if the leader fails before
+    /// the replicas have copied them.
+    Leader,
+    /// All the in-sync replicas acknowledge the write.
+    All,
+}
+
+impl Acks {
+    /// Returns the value of the `acks` configuration parameter.
+    fn as_config_value(&self) -> &'static str {
+        match *self {
+            Acks::None => "0",
+            Acks::Leader => "1",
+            Acks::All => "all",
+        }
+    }
+}
+
+/// The compression codec used by the producer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Compression {
+    /// No compression.
+    None,
+    /// Gzip compression.
+    Gzip,
+    /// Snappy compression.
+    Snappy,
+    /// LZ4 compression.
+    Lz4,
+}
+
+impl Compression {
+    /// Returns the value of the `compression.codec` configuration parameter.
+    fn as_config_value(&self) -> &'static str {
+        match *self {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+            Compression::Snappy => "snappy",
+            Compression::Lz4 => "lz4",
+        }
+    }
+}
+
+/// The partitioner used by the producer to assign a partition to messages with no explicit
+/// partition.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Partitioner {
+    /// Random distribution.
+    Random,
+    /// CRC32 hash of the key. Messages with an empty or no key are all sent to the same partition.
+    Consistent,
+    /// CRC32 hash of the key. Messages with an empty or no key are distributed randomly.
+    ConsistentRandom,
+}
+
+impl Partitioner {
+    /// Returns the value of the `partitioner` configuration parameter.
+    fn as_config_value(&self) -> &'static str {
+        match *self {
+            Partitioner::Random => "random",
+            Partitioner::Consistent => "consistent",
+            Partitioner::ConsistentRandom => "consistent_random",
+        }
+    }
+}
+
+/// The address family used to connect to the brokers.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BrokerAddressFamily {
+    /// Use any address returned by the DNS lookup.
+    Any,
+    /// Only use IPv4 addresses.
+    V4,
+    /// Only use IPv6 addresses.
+    V6,
+}
+
+impl BrokerAddressFamily {
+    /// Returns the value of the `broker.address.family` configuration parameter.
+    fn as_config_value(&self) -> &'static str {
+        match *self {
+            BrokerAddressFamily::Any => "any",
+            BrokerAddressFamily::V4 => "v4",
+            BrokerAddressFamily::V6 => "v6",
+        }
+    }
+}
+
+/// Returns true if the address has the form `[protocol://]host[:port]`, where the host is either a
+/// name, an IPv4 address or an IPv6 address in square brackets.
+fn is_valid_broker_address(address: &str) -> bool {
+    let address = match address.find("://") {
+        Some(index) => &address[index + 3..],
+        None => address,
+    };
+    let (host, port) = if address.starts_with('[') {
+        match address.find(']') {
+            Some(end) => {
+                let rest = &address[end + 1..];
+                if !rest.is_empty() && !rest.starts_with(':') {
+                    return false;
+                }
+                (&address[1..end], if rest.is_empty() { None } else { Some(&rest[1..]) })
+            },
+            None => return false,
+        }
+    } else {
+        let mut parts = address.splitn(2, ':');
+        (parts.next().unwrap_or(""), parts.next())
+    };
+    let valid_host = !host.is_empty() && !host.contains(|c: char| c.is_whitespace() || c == '/');
+    let valid_port = port.map(|port| port.parse::<u16>().is_ok()).unwrap_or(true);
+    valid_host && valid_port
+}
+
 //
 // ********** CLIENT CONFIG **********
 //
@@ -97,6 +223,13 @@ impl NativeClientConfig {
         mem::forget(self);
         ptr
     }
+
+    /// Returns a copy of the native configuration, including the topic configuration and the
+    /// callbacks already set. Since `ptr_move` consumes the configuration, this method can be used
+    /// to create multiple clients starting from the same native configuration.
+    pub fn duplicate(&self) -> NativeClientConfig {
+        NativeClientConfig::from_ptr(unsafe { rdsys::rd_kafka_conf_dup(self.ptr) })
+    }
 }
 
 impl Drop for NativeClientConfig {
@@ -111,6 +244,10 @@ impl Drop for NativeClientConfig {
 pub struct ClientConfig {
     conf_map: HashMap<String, String>,
     default_topic_config: Option<TopicConfig>,
+    topic_configs: HashMap<String, TopicConfig>,
+    // The value set with `set_bootstrap_servers`, validated when the client is created unless it
+    // has been overwritten with `set`.
+    typed_bootstrap_servers: Option<String>,
     pub log_level: RDKafkaLogLevel,
 }
 
@@ -126,6 +263,8 @@ impl ClientConfig {
         ClientConfig {
             conf_map: HashMap::new(),
             default_topic_config: None,
+            topic_configs: HashMap::new(),
+            typed_bootstrap_servers: None,
             log_level: log_level_from_global_config(),
         }
     }
@@ -136,6 +275,12 @@ impl ClientConfig {
         self
     }
 
+    /// Returns the value of a parameter in the configuration, or None if the parameter hasn't
+    /// been set. Default values used by librdkafka are not returned.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.conf_map.get(key).map(|value| value.as_str())
+    }
+
     /// Sets the default topic configuration to use for automatically subscribed
     /// topics (e.g., through pattern-matched topics).
     pub fn set_default_topic_config(&mut self, default_topic_config: TopicConfig) -> &mut ClientConfig {
@@ -143,6 +288,104 @@ impl ClientConfig {
         self
     }
 
+    /// Sets the topic configuration to use for a specific topic, overriding the default topic
+    /// configuration. Currently only producers support topic specific configurations.
+    pub fn set_topic_config(&mut self, topic: &str, topic_config: TopicConfig) -> &mut ClientConfig {
+        self.topic_configs.insert(topic.to_string(), topic_config);
+        self
+    }
+
+    /// Returns the topic specific configurations, indexed by topic name.
+    pub fn topic_configs(&self) -> &HashMap<String, TopicConfig> {
+        &self.topic_configs
+    }
+
+    /// Sets how many times the producer will retry sending a message (`retries`). Since retries
+    /// might cause reordering of the messages, enabling them together with an explicit
+    /// `max.in.flight.requests.per.connection` greater than 1 is rejected when the client is
+    /// created.
+    pub fn set_retries(&mut self, retries: u32) -> &mut ClientConfig {
+        self.set("retries", &retries.to_string())
+    }
+
+    /// Sets the time to wait before retrying a failed request (`retry.backoff.ms`). The duration
+    /// is truncated to milliseconds.
+    pub fn set_retry_backoff(&mut self, backoff: Duration) -> &mut ClientConfig {
+        self.set("retry.backoff.ms", &duration_to_millis(backoff).to_string())
+    }
+
+    /// Sets the time the producer will wait for the acknowledgement of a produce request from the
+    /// broker (`request.timeout.ms`). The duration is truncated to milliseconds.
+    pub fn set_request_timeout(&mut self, timeout: Duration) -> &mut ClientConfig {
+        self.set("request.timeout.ms", &duration_to_millis(timeout).to_string())
+    }
+
+    /// Sets how long the producer waits for messages to accumulate in its queue before sending
+    /// them to the brokers (`queue.buffering.max.ms`, also known as `linger.ms`). A higher value
+    /// allows larger and more efficient batches, but every message can spend up to this time in
+    /// the queue, in addition to the network and broker latency. The duration is truncated to
+    /// milliseconds.
+    pub fn set_linger(&mut self, linger: Duration) -> &mut ClientConfig {
+        self.set("queue.buffering.max.ms", &duration_to_millis(linger).to_string())
+    }
+
+    /// Sets the timeout of network requests (`socket.timeout.ms`). The duration is truncated to
+    /// milliseconds, and it must be between 10 milliseconds and 5 minutes: other values will cause
+    /// an error when the client is created.
+    pub fn set_socket_timeout(&mut self, timeout: Duration) -> &mut ClientConfig {
+        self.set("socket.timeout.ms", &duration_to_millis(timeout).to_string())
+    }
+
+    /// Sets the number of acknowledgements required by the producer (`acks`).
+    pub fn set_acks(&mut self, acks: Acks) -> &mut ClientConfig {
+        self.set("acks", acks.as_config_value())
+    }
+
+    /// Sets the compression codec used by the producer (`compression.codec`). The compression
+    /// level can't be configured with the bundled version of librdkafka: each codec uses its
+    /// default level.
+    pub fn set_compression(&mut self, codec: Compression) -> &mut ClientConfig {
+        self.set("compression.codec", codec.as_config_value())
+    }
+
+    /// Sets the partitioner used by the producer (`partitioner`). The murmur2 partitioner used by
+    /// the Java client is not available in the bundled version of librdkafka, so keyed messages
+    /// produced by Java and Rust clients to the same topic won't necessarily land on the same
+    /// partition.
+    pub fn set_partitioner(&mut self, partitioner: Partitioner) -> &mut ClientConfig {
+        self.set("partitioner", partitioner.as_config_value())
+    }
+
+    /// Sets the initial list of brokers (`bootstrap.servers`). Each broker is specified as
+    /// `host[:port]`, optionally preceded by the protocol (e.g. `SSL://`); IPv6 addresses must be
+    /// enclosed in square brackets. Malformed addresses, or an empty list, will cause an error
+    /// when the client is created. A value set directly with `set` is passed to librdkafka
+    /// unchecked.
+    pub fn set_bootstrap_servers(&mut self, servers: &[&str]) -> &mut ClientConfig {
+        let servers = servers.join(",");
+        self.typed_bootstrap_servers = Some(servers.clone());
+        self.set("bootstrap.servers", &servers)
+    }
+
+    /// Sets the address family used to connect to the brokers (`broker.address.family`). On
+    /// dual-stack hosts, restricting it to `V4` avoids connection attempts over IPv6 when the
+    /// network only supports IPv4.
+    pub fn set_broker_address_family(&mut self, family: BrokerAddressFamily) -> &mut ClientConfig {
+        self.set("broker.address.family", family.as_config_value())
+    }
+
+    /// Configures SASL/GSSAPI (Kerberos) authentication, setting the Kerberos service name, the
+    /// client principal and the keytab together. The security protocol (`SASL_PLAINTEXT` or
+    /// `SASL_SSL`) should be set separately. The keytab file is checked when the client is
+    /// created, and a `KafkaError::ClientConfig` error is returned if it can't be read. Requires
+    /// the `sasl` feature.
+    pub fn sasl_gssapi(&mut self, service_name: &str, principal: &str, keytab_path: &str) -> &mut ClientConfig {
+        self.set("sasl.mechanisms", "GSSAPI")
+            .set("sasl.kerberos.service.name", service_name)
+            .set("sasl.kerberos.principal", principal)
+            .set("sasl.kerberos.keytab", keytab_path)
+    }
+
     /// Sets the log level of the client. If not specified, the log level will be calculated based
     /// on the global log level of the log crate.
     pub fn set_log_level(&mut self, log_level: RDKafkaLogLevel) -> &mut ClientConfig {
@@ -150,8 +393,65 @@ impl ClientConfig {
         self
     }
 
+    /// Checks the configuration for combinations of parameters that are accepted by librdkafka,
+    /// but that are unlikely to be intended. Invalid values of the single parameters are reported
+    /// by librdkafka during the creation of the native configuration.
+    fn validate(&self) -> KafkaResult<()> {
+        if self.get("retry.backoff.ms") == Some("0") {
+            return Err(KafkaError::ClientConfig(
+                RDKafkaConfRes::RD_KAFKA_CONF_INVALID, "retry backoff must be at least 1ms".to_owned(),
+                "retry.backoff.ms".to_owned(), "0".to_owned()));
+        }
+        if self.get("request.timeout.ms") == Some("0") {
+            return Err(KafkaError::ClientConfig(
+                RDKafkaConfRes::RD_KAFKA_CONF_INVALID, "request timeout must be at least 1ms".to_owned(),
+                "request.timeout.ms".to_owned(), "0".to_owned()));
+        }
+        if let Some(timeout) = self.get("socket.timeout.ms") {
+            let (min, max) = SOCKET_TIMEOUT_RANGE_MS;
+            match timeout.parse::<u64>() {
+                Ok(timeout_ms) if timeout_ms >= min && timeout_ms <= max => {},
+                _ => return Err(KafkaError::ClientConfig(
+                    RDKafkaConfRes::RD_KAFKA_CONF_INVALID,
+                    format!("socket timeout must be between {}ms and {}ms", min, max),
+                    "socket.timeout.ms".to_owned(), timeout.to_owned())),
+            }
+        }
+        if let Some(keytab) = self.get("sasl.kerberos.keytab") {
+            if let Err(e) = File::open(keytab) {
+                return Err(KafkaError::ClientConfig(
+                    RDKafkaConfRes::RD_KAFKA_CONF_INVALID, format!("keytab can't be read: {}", e),
+                    "sasl.kerberos.keytab".to_owned(), keytab.to_owned()));
+            }
+        }
+        if let Some(ref servers) = self.typed_bootstrap_servers {
+            if self.get("bootstrap.servers") == Some(servers.as_str()) {
+                if let Some(address) = servers.split(',').find(|address| !is_valid_broker_address(address.trim())) {
+                    return Err(KafkaError::ClientConfig(
+                        RDKafkaConfRes::RD_KAFKA_CONF_INVALID,
+                        format!("invalid broker address: '{}'", address.trim()),
+                        "bootstrap.servers".to_owned(), servers.to_owned()));
+                }
+            }
+        }
+        let retries = self.get("retries").or_else(|| self.get("message.send.max.retries"))
+            .and_then(|retries| retries.parse::<u64>().ok());
+        let max_in_flight = self.get("max.in.flight.requests.per.connection")
+            .or_else(|| self.get("max.in.flight"));
+        if let (Some(retries), Some(max_in_flight)) = (retries, max_in_flight) {
+            if retries > 0 && max_in_flight.parse::<u64>().map(|max| max > 1).unwrap_or(false) {
+                return Err(KafkaError::ClientConfig(
+                    RDKafkaConfRes::RD_KAFKA_CONF_INVALID,
+                    "retries with more than one in flight request per connection might reorder messages".to_owned(),
+                    "max.in.flight.requests.per.connection".to_owned(), max_in_flight.to_owned()));
+            }
+        }
+        Ok(())
+    }
+
     /// Returns the native rdkafka-sys configuration.
     pub fn create_native_config(&self) -> KafkaResult<NativeClientConfig> {
+        self.validate()?;
         let conf = unsafe { rdsys::rd_kafka_conf_new() };
         let errstr = [0; ERR_LEN];
         for (key, value) in &self.conf_map {
@@ -214,24 +514,29 @@ pub trait FromClientConfigAndContext<C: Context>: Sized {
 //
 
 /// A native rdkafka-sys topic config.
-struct NativeTopicConfig {
+pub struct NativeTopicConfig {
     ptr: *mut RDKafkaTopicConf,
 }
 
 impl NativeTopicConfig {
     /// Wraps a pointer to an `RDKafkaTopicConf` object and returns a new `NativeTopicConfig`.
-    fn from_ptr(ptr: *mut RDKafkaTopicConf) -> NativeTopicConfig {
+    pub fn from_ptr(ptr: *mut RDKafkaTopicConf) -> NativeTopicConfig {
         NativeTopicConfig {ptr }
     }
 
     /// Returns the pointer to the librdkafka RDKafkaTopicConf structure. This method should be used
     /// when the native pointer is intended to be moved. The destructor won't be executed
     /// automatically; the caller should take care of deallocating the resource when no longer needed.
-    fn ptr_move(self) -> *mut RDKafkaTopicConf {
+    pub fn ptr_move(self) -> *mut RDKafkaTopicConf {
         let ptr = self.ptr;
         mem::forget(self);
         ptr
     }
+
+    /// Returns a copy of the native topic configuration.
+    pub fn duplicate(&self) -> NativeTopicConfig {
+        NativeTopicConfig::from_ptr(unsafe { rdsys::rd_kafka_topic_conf_dup(self.ptr) })
+    }
 }
 
 impl Drop for NativeTopicConfig {
@@ -268,7 +573,7 @@ impl TopicConfig {
     }
 
     /// Creates a native rdkafka-sys topic configuration.
-    fn create_native_config(&self) -> KafkaResult<NativeTopicConfig> {
+    pub fn create_native_config(&self) -> KafkaResult<NativeTopicConfig> {
         let config_ptr = unsafe { rdsys::rd_kafka_topic_conf_new() };
         let errstr = [0; ERR_LEN];
         for (name, value) in &self.conf_map {
@@ -286,3 +591,137 @@ impl TopicConfig {
         Ok(NativeTopicConfig::from_ptr(config_ptr))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    #[test]
+    fn test_typed_setters() {
+        let mut config = ClientConfig::new();
+        config.set_retries(3)
+            .set_retry_backoff(Duration::from_millis(250))
+            .set_request_timeout(Duration::from_secs(10))
+            .set_linger(Duration::from_millis(5));
+        assert_eq!(config.get("retries"), Some("3"));
+        assert_eq!(config.get("retry.backoff.ms"), Some("250"));
+        assert_eq!(config.get("request.timeout.ms"), Some("10000"));
+        assert_eq!(config.get("queue.buffering.max.ms"), Some("5"));
+        assert!(config.create_native_config().is_ok());
+
+        config.set_retry_backoff(Duration::from_millis(0));
+        assert!(config.create_native_config().is_err());
+    }
+
+    #[test]
+    fn test_retries_with_max_in_flight() {
+        let mut config = ClientConfig::new();
+        config.set_retries(3).set("max.in.flight.requests.per.connection", "1");
+        assert!(config.create_native_config().is_ok());
+
+        config.set("max.in.flight.requests.per.connection", "5");
+        match config.create_native_config() {
+            Err(KafkaError::ClientConfig(_, _, ref key, ref value)) => {
+                assert_eq!(key, "max.in.flight.requests.per.connection");
+                assert_eq!(value, "5");
+            },
+            _ => panic!("Invalid configuration accepted"),
+        }
+
+        config.set_retries(0);
+        assert!(config.create_native_config().is_ok());
+    }
+
+    #[test]
+    fn test_set_socket_timeout() {
+        let mut config = ClientConfig::new();
+        config.set_socket_timeout(Duration::from_secs(30));
+        assert_eq!(config.get("socket.timeout.ms"), Some("30000"));
+        assert!(config.create_native_config().is_ok());
+
+        for timeout in &[Duration::from_millis(5), Duration::from_secs(600)] {
+            config.set_socket_timeout(*timeout);
+            match config.create_native_config() {
+                Err(KafkaError::ClientConfig(_, _, ref key, _)) => assert_eq!(key, "socket.timeout.ms"),
+                _ => panic!("Expected a client config error for {:?}", timeout),
+            }
+        }
+    }
+
+    #[test]
+    fn test_set_acks() {
+        let mut config = ClientConfig::new();
+        config.set_acks(Acks::Leader);
+        assert_eq!(config.get("acks"), Some("1"));
+        config.set_acks(Acks::All);
+        assert_eq!(config.get("acks"), Some("all"));
+        config.set_acks(Acks::None);
+        assert_eq!(config.get("acks"), Some("0"));
+        assert!(config.create_native_config().is_ok());
+    }
+
+    #[test]
+    fn test_set_compression() {
+        let mut config = ClientConfig::new();
+        config.set_compression(Compression::Lz4);
+        assert_eq!(config.get("compression.codec"), Some("lz4"));
+        config.set_compression(Compression::Gzip);
+        assert_eq!(config.get("compression.codec"), Some("gzip"));
+        assert!(config.create_native_config().is_ok());
+    }
+
+    #[test]
+    fn test_set_partitioner() {
+        let mut config = ClientConfig::new();
+        config.set_partitioner(Partitioner::Consistent);
+        assert_eq!(config.get("partitioner"), Some("consistent"));
+        config.set_partitioner(Partitioner::ConsistentRandom);
+        assert_eq!(config.get("partitioner"), Some("consistent_random"));
+        assert!(config.create_native_config().is_ok());
+    }
+
+    #[test]
+    fn test_set_bootstrap_servers() {
+        let mut config = ClientConfig::new();
+        config.set_bootstrap_servers(&["broker1:9092", "SSL://broker2:9093", "[::1]:9092", "broker3"]);
+        assert_eq!(config.get("bootstrap.servers"), Some("broker1:9092,SSL://broker2:9093,[::1]:9092,broker3"));
+        assert!(config.create_native_config().is_ok());
+
+        for servers in &[&[][..], &["broker1:9092", ""][..], &["broker1:port"][..], &["[::1"][..], &[":9092"][..]] {
+            config.set_bootstrap_servers(servers);
+            match config.create_native_config() {
+                Err(KafkaError::ClientConfig(_, _, ref key, _)) => assert_eq!(key, "bootstrap.servers"),
+                _ => panic!("Expected a client config error for {:?}", servers),
+            }
+        }
+
+        // Values set directly are passed through to librdkafka.
+        config.set("bootstrap.servers", "broker1:port");
+        assert!(config.create_native_config().is_ok());
+    }
+
+    #[test]
+    fn test_set_broker_address_family() {
+        let mut config = ClientConfig::new();
+        config.set_broker_address_family(BrokerAddressFamily::V4);
+        assert_eq!(config.get("broker.address.family"), Some("v4"));
+        config.set_broker_address_family(BrokerAddressFamily::V6);
+        assert_eq!(config.get("broker.address.family"), Some("v6"));
+        config.set_broker_address_family(BrokerAddressFamily::Any);
+        assert_eq!(config.get("broker.address.family"), Some("any"));
+        assert!(config.create_native_config().is_ok());
+    }
+
+    #[test]
+    fn test_sasl_gssapi_missing_keytab() {
+        let mut config = ClientConfig::new();
+        config.sasl_gssapi("kafka", "client@EXAMPLE.COM", "/nonexistent/client.keytab");
+        assert_eq!(config.get("sasl.mechanisms"), Some("GSSAPI"));
+        match config.create_native_config() {
+            Err(KafkaError::ClientConfig(_, _, ref key, _)) => assert_eq!(key, "sasl.kerberos.keytab"),
+            _ => panic!("Expected a client config error"),
+        }
+    }
+}
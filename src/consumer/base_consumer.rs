@@ -1,22 +1,40 @@
 //! Low level consumer wrapper.
+use futures::{task, Async, Poll, Stream};
+use futures::task::Task;
+use libc;
 use rdsys;
 use rdsys::types::*;
 
-use client::{Client, NativeClient};
+use client::{Client, NativeClient, NativeQueue, OpaqueContext};
 use config::{FromClientConfig, FromClientConfigAndContext, ClientConfig};
 use consumer::{Consumer, ConsumerContext, CommitMode, EmptyConsumerContext};
 use error::{KafkaError, KafkaResult, IsError};
 use groups::GroupList;
 use message::{Message, BorrowedMessage};
 use metadata::Metadata;
-use topic_partition_list::TopicPartitionList;
-use topic_partition_list::Offset::Offset;
-use util::cstr_to_owned;
+use topic_partition_list::{Offset, TopicPartitionList};
+use util::{cstr_to_owned, duration_to_millis};
 
-use std::os::raw::c_void;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::os::raw::{c_int, c_void};
 use std::str;
 use std::mem;
 use std::ptr;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Sets the offsets of the newly assigned partitions to the starting offsets requested with
+/// `subscribe_with_offsets`, if any. Each starting offset is only applied once.
+fn apply_start_offsets(start_offsets: &Mutex<HashMap<(String, i32), Offset>>, tpl: &TopicPartitionList) {
+    let mut start_offsets = start_offsets.lock().unwrap();
+    for elem in tpl.elements() {
+        if let Some(offset) = start_offsets.remove(&(elem.topic().into_owned(), elem.partition())) {
+            elem.set_offset(offset);
+        }
+    }
+}
 
 pub unsafe extern "C" fn native_commit_cb<C: ConsumerContext>(
     _conf: *mut RDKafka,
@@ -24,16 +42,14 @@ pub unsafe extern "C" fn native_commit_cb<C: ConsumerContext>(
     offsets: *mut RDKafkaTopicPartitionList,
     opaque_ptr: *mut c_void,
 ) {
-    let context = Box::from_raw(opaque_ptr as *mut C);
+    let context = OpaqueContext::<C>::from_opaque(opaque_ptr).context();
 
     let commit_error = if err.is_error() {
         Err(KafkaError::ConsumerCommit(err.into()))
     } else {
         Ok(())
     };
-    (*context).commit_callback(commit_error, offsets);
-
-    mem::forget(context); // Do not free the context
+    context.commit_callback(commit_error, offsets);
 }
 
 /// Native rebalance callback. This callback will run on every rebalance, and it will call the
@@ -44,14 +60,28 @@ unsafe extern "C" fn native_rebalance_cb<C: ConsumerContext>(
     native_tpl: *mut RDKafkaTopicPartitionList,
     opaque_ptr: *mut c_void,
 ) {
-    // let context: &C = &*(opaque_ptr as *const C);
-    let context = Box::from_raw(opaque_ptr as *mut C);
+    let opaque_context = OpaqueContext::<C>::from_opaque(opaque_ptr);
+    let context = opaque_context.context();
     let native_client = NativeClient::from_ptr(rk);
     let tpl = TopicPartitionList::from_ptr(native_tpl);
 
+    if err == RDKafkaRespErr::RD_KAFKA_RESP_ERR__ASSIGN_PARTITIONS {
+        apply_start_offsets(opaque_context.start_offsets(), &tpl);
+    }
     context.rebalance(&native_client, err, &tpl);
+    if err == RDKafkaRespErr::RD_KAFKA_RESP_ERR__ASSIGN_PARTITIONS {
+        if opaque_context.pause_assignments() {
+            // The assignment decided by the context is paused, so that no message is fetched
+            // before `wait_for_assignment` returns.
+            let mut assignment_ptr = ptr::null_mut();
+            if !rdsys::rd_kafka_assignment(rk, &mut assignment_ptr).is_error() {
+                let assignment = TopicPartitionList::from_ptr(assignment_ptr);
+                rdsys::rd_kafka_pause_partitions(rk, assignment.ptr());
+            }
+        }
+        opaque_context.notify_assignment();
+    }
 
-    mem::forget(context); // Do not free the context
     mem::forget(native_client); // Do not free native client
     tpl.leak() // Do not free native topic partition list
 }
@@ -61,6 +91,8 @@ unsafe extern "C" fn native_rebalance_cb<C: ConsumerContext>(
 /// to make progress on rebalance, callbacks and to receive messages.
 pub struct BaseConsumer<C: ConsumerContext> {
     client: Client<C>,
+    // Consumers without a group id can only be used with manual assignment.
+    has_group_id: bool,
 }
 
 impl FromClientConfig for BaseConsumer<EmptyConsumerContext> {
@@ -79,7 +111,8 @@ impl<C: ConsumerContext> FromClientConfigAndContext<C> for BaseConsumer<C> {
         }
         let client = Client::new(config, native_config, RDKafkaType::RD_KAFKA_CONSUMER, context)?;
         unsafe { rdsys::rd_kafka_poll_set_consumer(client.native_ptr()) };
-        Ok(BaseConsumer { client })
+        let has_group_id = config.get("group.id").map(|group_id| !group_id.is_empty()).unwrap_or(false);
+        Ok(BaseConsumer { client, has_group_id })
     }
 }
 
@@ -101,12 +134,146 @@ impl<C: ConsumerContext> BaseConsumer<C> {
     /// to serve any queued callbacks waiting to be called. This is especially important for
     /// automatic consumer rebalance, as the rebalance function will be executed by the thread
     /// calling the poll() function.
+    /// ## Consumer queue
+    /// The consumer is polled with `rd_kafka_consumer_poll`. When the consumer is created, the
+    /// main librdkafka queue is redirected to the consumer queue, so this method serves both the
+    /// messages and all the other events of the client, including rebalances, commit callbacks
+    /// and errors. This is the case both for consumers that join a group with `subscribe` and for
+    /// consumers whose partitions are assigned manually with `assign`: `Client::poll` should not
+    /// be used in either case.
     /// ## Lifetime
     /// The returned message lives in the memory of the consumer and cannot outlive it.
     pub fn poll(&self, timeout_ms: i32) -> Option<KafkaResult<BorrowedMessage>> {
         self.poll_raw(timeout_ms)
             .map(|ptr| unsafe { BorrowedMessage::from_consumer(ptr, self) })
     }
+
+    /// Polls the consumer to serve the rebalance callback, until at least one partition is
+    /// assigned or the timeout expires. Used by `wait_for_assignment`, which pauses the assigned
+    /// partitions, so no message is returned by the poll.
+    fn poll_until_assigned(&self, timeout_ms: i32) -> KafkaResult<TopicPartitionList> {
+        let start_time = Instant::now();
+        loop {
+            let assignment = self.assignment()?;
+            let elapsed_ms = duration_to_millis(start_time.elapsed()) as i32;
+            if assignment.count() > 0 || (timeout_ms >= 0 && elapsed_ms >= timeout_ms) {
+                return Ok(assignment);
+            }
+            let poll_timeout_ms = if timeout_ms < 0 { 100 } else { (timeout_ms - elapsed_ms).min(100) };
+            if let Some(message_ptr) = self.poll_raw(poll_timeout_ms) {
+                unsafe { rdsys::rd_kafka_message_destroy(message_ptr) };
+            }
+        }
+    }
+
+    /// Returns a stream of the messages of the consumer. Unlike the `StreamConsumer`, the
+    /// consumer is not polled by a background thread: it is polled directly by the task consuming
+    /// the stream, which makes it suitable to run many consumers on the same executor. See
+    /// `BaseMessageStream` for details.
+    #[cfg(unix)]
+    pub fn stream(&self) -> BaseMessageStream<C> {
+        BaseMessageStream::new(self)
+    }
+}
+
+/// A stream of messages polled from a `BaseConsumer` by the task consuming the stream.
+///
+/// Every time the stream is polled, the consumer is polled without blocking. If no message is
+/// available the stream returns `NotReady`, and the task is notified as soon as librdkafka
+/// enqueues a new event in the consumer queue: librdkafka writes to a pipe
+/// (`rd_kafka_queue_io_event_enable`), and a helper thread waiting on the pipe wakes up the task.
+/// The helper thread never polls the consumer, and the stream doesn't use any CPU while the
+/// consumer is idle. The stream never terminates.
+#[cfg(unix)]
+pub struct BaseMessageStream<'a, C: ConsumerContext + 'a> {
+    consumer: &'a BaseConsumer<C>,
+    queue: NativeQueue,
+    task: Arc<Mutex<Option<Task>>>,
+    write_fd: c_int,
+    waker: Option<JoinHandle<()>>,
+}
+
+#[cfg(unix)]
+impl<'a, C: ConsumerContext + 'a> BaseMessageStream<'a, C> {
+    fn new(consumer: &'a BaseConsumer<C>) -> BaseMessageStream<'a, C> {
+        let mut fds: [c_int; 2] = [-1, -1];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            panic!("Failed to create wakeup pipe: {}", io::Error::last_os_error());
+        }
+        let (read_fd, write_fd) = (fds[0], fds[1]);
+        // librdkafka writes from its internal threads: a full pipe must not block them.
+        unsafe { libc::fcntl(write_fd, libc::F_SETFL, libc::O_NONBLOCK) };
+
+        let task = Arc::new(Mutex::new(None::<Task>));
+        let task_clone = task.clone();
+        let waker = thread::Builder::new()
+            .name(format!("rdkafka-waker-{}", consumer.client.name()))
+            .spawn(move || {
+                let mut buf = [0u8; 64];
+                loop {
+                    let n = unsafe { libc::read(read_fd, buf.as_mut_ptr() as *mut c_void, buf.len()) };
+                    if n < 0 && io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                        continue;
+                    }
+                    if n <= 0 {
+                        // The write end was closed: the stream has been dropped.
+                        break;
+                    }
+                    if let Some(ref task) = *task_clone.lock().unwrap() {
+                        task.notify();
+                    }
+                }
+                unsafe { libc::close(read_fd) };
+            })
+            .expect("Failed to start waker thread");
+
+        let queue = NativeQueue::from_ptr(unsafe { rdsys::rd_kafka_queue_get_consumer(consumer.client.native_ptr()) });
+        let payload = [1u8];
+        unsafe {
+            rdsys::rd_kafka_queue_io_event_enable(queue.ptr(), write_fd, payload.as_ptr() as *const c_void, payload.len())
+        };
+
+        BaseMessageStream {
+            consumer: consumer,
+            queue: queue,
+            task: task,
+            write_fd: write_fd,
+            waker: Some(waker),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl<'a, C: ConsumerContext + 'a> Stream for BaseMessageStream<'a, C> {
+    type Item = KafkaResult<BorrowedMessage<'a>>;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        // The task is registered before polling, so that an event enqueued right after an empty
+        // poll will wake it up.
+        *self.task.lock().unwrap() = Some(task::current());
+        match self.consumer.poll(0) {
+            Some(result) => Ok(Async::Ready(Some(result))),
+            None => Ok(Async::NotReady),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl<'a, C: ConsumerContext + 'a> Drop for BaseMessageStream<'a, C> {
+    fn drop(&mut self) {
+        trace!("Destroying BaseMessageStream");
+        unsafe {
+            rdsys::rd_kafka_queue_io_event_enable(self.queue.ptr(), -1, ptr::null(), 0);
+            libc::close(self.write_fd);
+        }
+        if let Some(handle) = self.waker.take() {
+            if handle.join().is_err() {
+                warn!("Failure while terminating waker thread");
+            }
+        }
+        trace!("BaseMessageStream destroyed");
+    }
 }
 
 impl<C: ConsumerContext> Consumer<C> for BaseConsumer<C> {
@@ -114,7 +281,17 @@ impl<C: ConsumerContext> Consumer<C> for BaseConsumer<C> {
         self
     }
 
+    fn client(&self) -> &Client<C> {
+        &self.client
+    }
+
     fn subscribe(&self, topics: &[&str]) -> KafkaResult<()> {
+        if !self.has_group_id {
+            return Err(KafkaError::ClientConfig(
+                RDKafkaConfRes::RD_KAFKA_CONF_INVALID,
+                "group.id is required for subscription-based consumers".to_owned(),
+                "group.id".to_owned(), String::new()));
+        }
         let mut tpl = TopicPartitionList::new();
         for topic in topics {
             tpl.add_topic_unassigned(topic);
@@ -127,10 +304,64 @@ impl<C: ConsumerContext> Consumer<C> for BaseConsumer<C> {
         Ok(())
     }
 
+    fn subscribe_checked(&self, topics: &[&str], timeout_ms: i32) -> KafkaResult<()> {
+        let metadata = self.client.fetch_metadata(None, timeout_ms)?;
+        let missing_topics = topics.iter()
+            .filter(|topic| !topic.starts_with('^'))
+            .filter(|topic| !metadata.topics().iter().any(|topic_metadata| topic_metadata.name() == **topic))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !missing_topics.is_empty() {
+            return Err(KafkaError::Subscription(format!("unknown topics: {}", missing_topics.join(", "))));
+        }
+        self.subscribe(topics)
+    }
+
     fn unsubscribe(&self) {
         unsafe { rdsys::rd_kafka_unsubscribe(self.client.native_ptr()) };
     }
 
+    fn subscribe_with_offsets(&self, offsets: &TopicPartitionList) -> KafkaResult<()> {
+        let mut topics = Vec::new();
+        for elem in offsets.elements() {
+            if !topics.iter().any(|topic: &String| *topic == elem.topic()) {
+                topics.push(elem.topic().into_owned());
+            }
+        }
+        // The offsets are stored before subscribing, since the rebalance might be served by
+        // another thread polling the consumer, and removed if the subscription fails.
+        let start_offsets = self.client.opaque_context().start_offsets();
+        *start_offsets.lock().unwrap() = offsets.to_topic_map();
+        let result = self.subscribe(&topics.iter().map(|topic| topic.as_str()).collect::<Vec<_>>());
+        if result.is_err() {
+            start_offsets.lock().unwrap().clear();
+        }
+        result
+    }
+
+    fn resubscribe(&self, topics: &[&str], timeout_ms: i32) -> KafkaResult<TopicPartitionList> {
+        let new_topics = topics.iter().cloned().collect::<HashSet<_>>();
+        let current_subscription = self.subscription()?;
+        let current_topics = current_subscription.elements().iter()
+            .map(|elem| elem.topic().into_owned())
+            .collect::<HashSet<_>>();
+        if current_topics.len() == new_topics.len() && new_topics.iter().all(|t| current_topics.contains(*t)) {
+            // The subscription is not changing, no rebalance will be triggered.
+            return self.assignment();
+        }
+
+        // The new subscription always triggers a rebalance, which completes with an assignment,
+        // even if it's empty or the same as the current one.
+        let assignment_count = self.client.opaque_context().assignment_count();
+        self.subscribe(topics)?;
+
+        let timeout = if timeout_ms < 0 { None } else { Some(Duration::from_millis(timeout_ms as u64)) };
+        if !self.client.opaque_context().wait_for_assignment_after(assignment_count, timeout) {
+            return Err(KafkaError::Subscription("Timed out waiting for the new assignment".to_owned()));
+        }
+        self.assignment()
+    }
+
     fn assign(&self, assignment: &TopicPartitionList) -> KafkaResult<()> {
         let ret_code = unsafe { rdsys::rd_kafka_assign(self.client.native_ptr(), assignment.ptr()) };
         if ret_code.is_error() {
@@ -140,6 +371,56 @@ impl<C: ConsumerContext> Consumer<C> for BaseConsumer<C> {
         Ok(())
     }
 
+    fn pause(&self, partitions: &TopicPartitionList) -> KafkaResult<()> {
+        let ret_code = unsafe { rdsys::rd_kafka_pause_partitions(self.client.native_ptr(), partitions.ptr()) };
+        if ret_code.is_error() {
+            let error = unsafe { cstr_to_owned(rdsys::rd_kafka_err2str(ret_code)) };
+            return Err(KafkaError::PauseResume(error));
+        };
+        Ok(())
+    }
+
+    fn resume(&self, partitions: &TopicPartitionList) -> KafkaResult<()> {
+        let ret_code = unsafe { rdsys::rd_kafka_resume_partitions(self.client.native_ptr(), partitions.ptr()) };
+        if ret_code.is_error() {
+            let error = unsafe { cstr_to_owned(rdsys::rd_kafka_err2str(ret_code)) };
+            return Err(KafkaError::PauseResume(error));
+        };
+        Ok(())
+    }
+
+    fn seek(&self, topic: &str, partition: i32, offset: Offset, timeout_ms: i32) -> KafkaResult<()> {
+        let native_topic = self.client.native_topic(topic)?;
+        let ret_code = unsafe { rdsys::rd_kafka_seek(native_topic.ptr(), partition, offset.to_raw(), timeout_ms) };
+        if ret_code.is_error() {
+            let error = unsafe { cstr_to_owned(rdsys::rd_kafka_err2str(ret_code)) };
+            return Err(KafkaError::Seek(error));
+        };
+        Ok(())
+    }
+
+    fn seek_partitions(&self, partitions: &TopicPartitionList, timeout_ms: i32) -> KafkaResult<TopicPartitionList> {
+        let results = partitions.clone();
+        let mut errors = Vec::with_capacity(results.count());
+        for elem in results.elements() {
+            let native_topic = self.client.native_topic(&elem.topic())?;
+            errors.push(unsafe {
+                rdsys::rd_kafka_seek(native_topic.ptr(), elem.partition(), elem.offset().to_raw(), timeout_ms)
+            });
+        }
+        for (i, error) in errors.into_iter().enumerate() {
+            unsafe { (*(*results.ptr()).elems.offset(i as isize)).err = error };
+        }
+        Ok(results)
+    }
+
+    fn pause_and_seek(&self, topic: &str, partition: i32, offset: Offset, timeout_ms: i32) -> KafkaResult<()> {
+        let mut tpl = TopicPartitionList::new();
+        tpl.add_partition(topic, partition);
+        self.pause(&tpl)?;
+        self.seek(topic, partition, offset, timeout_ms)
+    }
+
     fn commit(&self, topic_partition_list: &TopicPartitionList, mode: CommitMode) -> KafkaResult<()> {
         let error = unsafe {
             rdsys::rd_kafka_commit(self.client.native_ptr(), topic_partition_list.ptr(), mode as i32)
@@ -171,6 +452,12 @@ impl<C: ConsumerContext> Consumer<C> for BaseConsumer<C> {
         }
     }
 
+    fn commit_message_offset<M: Message>(&self, message: &M, mode: CommitMode) -> KafkaResult<()> {
+        let mut tpl = TopicPartitionList::with_capacity(1);
+        tpl.add_partition_offset(&message.topic(), message.partition(), Offset::Offset(message.offset() + 1));
+        self.commit(&tpl, mode)
+    }
+
     fn store_offset(&self, message: &BorrowedMessage) -> KafkaResult<()> {
         let error = unsafe { rdsys::rd_kafka_offset_store(message.topic_ptr(), message.partition(), message.offset()) };
         if error.is_error() {
@@ -180,6 +467,31 @@ impl<C: ConsumerContext> Consumer<C> for BaseConsumer<C> {
         }
     }
 
+    fn store_offsets(&self, offsets: &TopicPartitionList) -> KafkaResult<TopicPartitionList> {
+        let results = offsets.clone();
+        let error = unsafe { rdsys::rd_kafka_offsets_store(self.client.native_ptr(), results.ptr()) };
+        if error.is_error() {
+            Err(KafkaError::StoreOffset(error.into()))
+        } else {
+            Ok(results)
+        }
+    }
+
+    fn store_message_offsets<M: Message>(&self, messages: &[M]) -> KafkaResult<TopicPartitionList> {
+        let mut max_offsets = HashMap::new();
+        for message in messages {
+            let max_offset = max_offsets.entry((message.topic(), message.partition())).or_insert(message.offset());
+            if message.offset() > *max_offset {
+                *max_offset = message.offset();
+            }
+        }
+        let mut offsets = TopicPartitionList::with_capacity(max_offsets.len());
+        for ((topic, partition), offset) in max_offsets {
+            offsets.add_partition_offset(&topic, partition, Offset::Offset(offset + 1));
+        }
+        self.store_offsets(&offsets)
+    }
+
     fn subscription(&self) -> KafkaResult<TopicPartitionList> {
         let mut tpl_ptr = ptr::null_mut();
         let error = unsafe { rdsys::rd_kafka_subscription(self.client.native_ptr(), &mut tpl_ptr) };
@@ -191,6 +503,15 @@ impl<C: ConsumerContext> Consumer<C> for BaseConsumer<C> {
         }
     }
 
+    fn subscription_patterns(&self) -> KafkaResult<Vec<String>> {
+        let subscription = self.subscription()?;
+        let patterns = subscription.elements().iter()
+            .map(|elem| elem.topic().into_owned())
+            .filter(|topic| topic.starts_with('^'))
+            .collect();
+        Ok(patterns)
+    }
+
     fn assignment(&self) -> KafkaResult<TopicPartitionList> {
         let mut tpl_ptr = ptr::null_mut();
         let error = unsafe { rdsys::rd_kafka_assignment(self.client.native_ptr(), &mut tpl_ptr) };
@@ -202,6 +523,21 @@ impl<C: ConsumerContext> Consumer<C> for BaseConsumer<C> {
         }
     }
 
+    fn wait_for_assignment(&self, timeout_ms: i32) -> KafkaResult<TopicPartitionList> {
+        let opaque_context = self.client.opaque_context();
+        let assignment_count = opaque_context.assignment_count();
+        // The partitions are paused as soon as they are assigned, so that the poll serving the
+        // rebalance callback doesn't consume (and automatically store) any message.
+        opaque_context.set_pause_assignments(true);
+        let assignment = self.poll_until_assigned(timeout_ms);
+        opaque_context.set_pause_assignments(false);
+        let assignment = assignment?;
+        if opaque_context.assignment_count() != assignment_count {
+            self.resume(&assignment)?;
+        }
+        Ok(assignment)
+    }
+
     fn committed(&self, timeout_ms: i32) -> KafkaResult<TopicPartitionList> {
         let mut tpl_ptr = ptr::null_mut();
         let assignment_error = unsafe { rdsys::rd_kafka_assignment(self.client.native_ptr(), &mut tpl_ptr) };
@@ -227,7 +563,7 @@ impl<C: ConsumerContext> Consumer<C> for BaseConsumer<C> {
         let mut tpl = unsafe { TopicPartitionList::from_ptr(tpl_ptr) };
 
         // Set the timestamp we want in the offset field for every partition as librdkafka expects.
-        tpl.set_all_offsets(Offset(timestamp));
+        tpl.set_all_offsets(Offset::Offset(timestamp));
 
         // This call will then put the offset in the offset field of this topic partition list.
         let offsets_for_times_error =
@@ -255,10 +591,63 @@ impl<C: ConsumerContext> Consumer<C> for BaseConsumer<C> {
         }
     }
 
+    fn effective_position(&self, timeout_ms: i32) -> KafkaResult<TopicPartitionList> {
+        let position = self.position()?;
+        if position.elements().iter().all(|elem| elem.offset() != Offset::Invalid) {
+            return Ok(position);
+        }
+        let committed = self.committed(timeout_ms)?;
+        for elem in position.elements() {
+            if elem.offset() == Offset::Invalid {
+                if let Some(committed_elem) = committed.find_partition(&elem.topic(), elem.partition()) {
+                    elem.set_offset(committed_elem.offset());
+                }
+            }
+        }
+        Ok(position)
+    }
+
     fn fetch_metadata(&self, topic: Option<&str>, timeout_ms: i32) -> KafkaResult<Metadata> {
         self.client.fetch_metadata(topic, timeout_ms)
     }
 
+    fn refresh_metadata(&self, timeout_ms: i32) -> KafkaResult<()> {
+        self.client.fetch_metadata_for_topics(&[], timeout_ms).map(|_| ())
+    }
+
+    fn topic_exists(&self, topic: &str, timeout_ms: i32) -> KafkaResult<bool> {
+        self.client.topic_exists(topic, timeout_ms)
+    }
+
+    fn fetch_metadata_for_topics(&self, topics: &[&str], timeout_ms: i32) -> KafkaResult<Metadata> {
+        self.client.fetch_metadata_for_topics(topics, timeout_ms)
+    }
+
+    fn all_partitions(&self, topics: &[&str], timeout_ms: i32) -> KafkaResult<TopicPartitionList> {
+        let metadata = self.fetch_metadata_for_topics(topics, timeout_ms)?;
+        let mut tpl = TopicPartitionList::new();
+        for topic in topics {
+            let topic_metadata = metadata.topics().iter()
+                .find(|topic_metadata| topic_metadata.name() == *topic);
+            let topic_metadata = match topic_metadata {
+                Some(topic_metadata) => topic_metadata,
+                None => return Err(KafkaError::MetadataFetch(
+                    RDKafkaRespErr::RD_KAFKA_RESP_ERR_UNKNOWN_TOPIC_OR_PART.into())),
+            };
+            if let Some(error) = topic_metadata.error() {
+                return Err(KafkaError::MetadataFetch(error.into()));
+            }
+            let mut partition_ids = topic_metadata.partitions().iter()
+                .map(|partition| partition.id())
+                .collect::<Vec<_>>();
+            partition_ids.sort();
+            for partition_id in partition_ids {
+                tpl.add_partition(topic, partition_id);
+            }
+        }
+        Ok(tpl)
+    }
+
     fn fetch_watermarks(&self, topic: &str, partition: i32, timeout_ms: i32) -> KafkaResult<(i64, i64)> {
         self.client
             .fetch_watermarks(topic, partition, timeout_ms)
@@ -267,6 +656,58 @@ impl<C: ConsumerContext> Consumer<C> for BaseConsumer<C> {
     fn fetch_group_list(&self, group: Option<&str>, timeout_ms: i32) -> KafkaResult<GroupList> {
         self.client.fetch_group_list(group, timeout_ms)
     }
+
+    fn fetch_lag(&self, timeout_ms: i32) -> KafkaResult<HashMap<(String, i32), i64>> {
+        let position = self.position()?;
+        let committed = if position.elements().iter().all(|elem| elem.offset() != Offset::Invalid) {
+            None
+        } else {
+            Some(self.committed(timeout_ms)?)
+        };
+        let mut lag_map = HashMap::with_capacity(position.count());
+        for elem in position.elements() {
+            let (low, high) = self.fetch_watermarks(&elem.topic(), elem.partition(), timeout_ms)?;
+            let offset = match elem.offset() {
+                Offset::Offset(offset) => Some(offset),
+                _ => committed.as_ref()
+                    .and_then(|committed| committed.find_partition(&elem.topic(), elem.partition()))
+                    .and_then(|committed_elem| match committed_elem.offset() {
+                        Offset::Offset(offset) => Some(offset),
+                        _ => None,
+                    }),
+            };
+            let lag = match offset {
+                Some(offset) if offset < high => high - offset,
+                Some(_) => 0,
+                None => high - low,
+            };
+            lag_map.insert((elem.topic().into_owned(), elem.partition()), lag);
+        }
+        Ok(lag_map)
+    }
+
+    fn member_id(&self) -> Option<String> {
+        let member_id_ptr = unsafe { rdsys::rd_kafka_memberid(self.client.native_ptr()) };
+        if member_id_ptr.is_null() {
+            return None;
+        }
+        let member_id = unsafe { cstr_to_owned(member_id_ptr) };
+        unsafe { rdsys::rd_kafka_mem_free(self.client.native_ptr(), member_id_ptr as *mut c_void) };
+        if member_id.is_empty() {
+            None
+        } else {
+            Some(member_id)
+        }
+    }
+
+    fn fetch_queue_len(&self) -> usize {
+        let queue_ptr = unsafe { rdsys::rd_kafka_queue_get_consumer(self.client.native_ptr()) };
+        if queue_ptr.is_null() {
+            return 0;
+        }
+        let queue = NativeQueue::from_ptr(queue_ptr);
+        unsafe { rdsys::rd_kafka_queue_length(queue.ptr()) }
+    }
 }
 
 impl<C: ConsumerContext> Drop for BaseConsumer<C> {
@@ -276,3 +717,58 @@ impl<C: ConsumerContext> Drop for BaseConsumer<C> {
         trace!("Consumer destroyed: {:?}", self.client.native_ptr());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base_consumer_subscribe_without_group_id() {
+        let consumer = ClientConfig::new().create::<BaseConsumer<_>>().unwrap();
+        match consumer.subscribe(&["topic"]) {
+            Err(KafkaError::ClientConfig(_, _, ref key, _)) => assert_eq!(key, "group.id"),
+            result => panic!("Unexpected subscription result: {:?}", result),
+        }
+
+        let consumer = ClientConfig::new()
+            .set("group.id", "group")
+            .create::<BaseConsumer<_>>()
+            .unwrap();
+        assert!(consumer.subscribe(&["topic"]).is_ok());
+    }
+
+    // The starting offsets are stored with the client, and each of them is only applied once.
+    #[test]
+    fn test_base_consumer_start_offsets() {
+        let consumer = ClientConfig::new()
+            .set("group.id", "group")
+            .create::<BaseConsumer<_>>()
+            .unwrap();
+        let mut offsets = TopicPartitionList::new();
+        offsets.add_partition_offset("topic", 0, Offset::Offset(10));
+        offsets.add_partition_offset("topic", 1, Offset::Offset(20));
+        consumer.subscribe_with_offsets(&offsets).unwrap();
+
+        let start_offsets = consumer.client().opaque_context().start_offsets();
+        let mut assignment = TopicPartitionList::new();
+        assignment.add_partition("topic", 0);
+        apply_start_offsets(start_offsets, &assignment);
+        assert_eq!(assignment.find_partition("topic", 0).unwrap().offset(), Offset::Offset(10));
+
+        let mut reassignment = TopicPartitionList::new();
+        reassignment.add_partition("topic", 0);
+        apply_start_offsets(start_offsets, &reassignment);
+        assert_eq!(reassignment.find_partition("topic", 0).unwrap().offset(), Offset::Invalid);
+        assert_eq!(start_offsets.lock().unwrap().len(), 1);
+    }
+
+    // The starting offsets should not be kept if the subscription fails.
+    #[test]
+    fn test_base_consumer_start_offsets_failed_subscription() {
+        let consumer = ClientConfig::new().create::<BaseConsumer<_>>().unwrap();
+        let mut offsets = TopicPartitionList::new();
+        offsets.add_partition_offset("topic", 0, Offset::Offset(10));
+        assert!(consumer.subscribe_with_offsets(&offsets).is_err());
+        assert!(consumer.client().opaque_context().start_offsets().lock().unwrap().is_empty());
+    }
+}
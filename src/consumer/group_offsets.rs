@@ -0,0 +1,84 @@
+//! Offsets of consumer groups the client is not a member of.
+//!
+//! The functions in this module use a temporary consumer configured with the `group.id` of the
+//! target group. Since the consumer never subscribes, it doesn't join the group and it doesn't
+//! trigger any rebalance: the offsets are fetched from, and committed to, the group coordinator
+//! directly.
+use rdsys;
+use rdsys::types::*;
+
+use std::os::raw::c_void;
+use std::ptr;
+
+use config::ClientConfig;
+use consumer::{BaseConsumer, Consumer, EmptyConsumerContext};
+use error::{IsError, KafkaError, KafkaResult};
+use topic_partition_list::TopicPartitionList;
+
+/// Creates a consumer that can read the offsets of the specified group, without joining it.
+fn create_group_client(config: &ClientConfig, group_id: &str) -> KafkaResult<BaseConsumer<EmptyConsumerContext>> {
+    let mut config = config.clone();
+    config.set("group.id", group_id)
+        .set("enable.auto.commit", "false");
+    config.create::<BaseConsumer<_>>()
+}
+
+/// Returns the offsets committed by the consumer group `group_id` for the specified partitions,
+/// without joining the group. The configuration is used to create a temporary consumer, and it
+/// should contain at least `bootstrap.servers`; its `group.id` is replaced with `group_id`.
+/// Partitions with no committed offset are returned with `Offset::Invalid`. This can be used, for
+/// instance, to copy the offsets of a group to a new group during a migration.
+pub fn committed_offsets_for_group(config: &ClientConfig, group_id: &str, partitions: &TopicPartitionList,
+                                   timeout_ms: i32) -> KafkaResult<TopicPartitionList> {
+    let consumer = create_group_client(config, group_id)?;
+    let offsets = partitions.clone();
+    let committed_error = unsafe {
+        rdsys::rd_kafka_committed(consumer.client().native_ptr(), offsets.ptr(), timeout_ms)
+    };
+    if committed_error.is_error() {
+        Err(KafkaError::MetadataFetch(committed_error.into()))
+    } else {
+        Ok(offsets)
+    }
+}
+
+/// Stores the result of the commit in the `Option` pointed by `opaque`.
+unsafe extern "C" fn native_group_commit_cb(
+    _rk: *mut RDKafka,
+    err: RDKafkaRespErr,
+    offsets: *mut RDKafkaTopicPartitionList,
+    opaque: *mut c_void,
+) {
+    let result = &mut *(opaque as *mut Option<(RDKafkaRespErr, Option<TopicPartitionList>)>);
+    let offsets = if offsets.is_null() {
+        None
+    } else {
+        // The list is owned by librdkafka.
+        Some(TopicPartitionList::from_ptr(rdsys::rd_kafka_topic_partition_list_copy(offsets)))
+    };
+    *result = Some((err, offsets));
+}
+
+/// Commits the specified offsets for the consumer group `group_id`, without joining the group. The
+/// configuration is used as in `committed_offsets_for_group`. The offsets can be used to reset the
+/// position of a group, for instance to reprocess a topic: the group should have no active
+/// members, otherwise the coordinator will reject the commit. The call blocks until the commit
+/// completes, and returns the committed offsets, each one with the result of the commit of its
+/// partition.
+pub fn commit_offsets_for_group(config: &ClientConfig, group_id: &str, offsets: &TopicPartitionList)
+        -> KafkaResult<TopicPartitionList> {
+    let consumer = create_group_client(config, group_id)?;
+    let mut result: Option<(RDKafkaRespErr, Option<TopicPartitionList>)> = None;
+    // Without a queue, the callback is served by this call before returning.
+    let commit_error = unsafe {
+        rdsys::rd_kafka_commit_queue(consumer.client().native_ptr(), offsets.ptr(), ptr::null_mut(),
+                                     Some(native_group_commit_cb),
+                                     &mut result as *mut _ as *mut c_void)
+    };
+    let (err, committed) = result.unwrap_or((commit_error, None));
+    if err.is_error() {
+        Err(KafkaError::ConsumerCommit(err.into()))
+    } else {
+        Ok(committed.unwrap_or_else(|| offsets.clone()))
+    }
+}
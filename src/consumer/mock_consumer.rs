@@ -0,0 +1,252 @@
+//! In-memory consumer, for unit testing code that consumes from Kafka.
+use futures::{Async, Poll, Stream};
+
+use error::{KafkaError, KafkaResult};
+use message::{Message, OwnedMessage};
+use topic_partition_list::{Offset, TopicPartitionList};
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Consumption state of the `MockConsumer`.
+struct MockConsumerState {
+    // Next offset to be returned for each partition.
+    positions: HashMap<(String, i32), i64>,
+    // If false, every partition is consumed from the beginning.
+    assigned: bool,
+    // Offsets stored with `store_offsets`, used when a partition is assigned with `Offset::Stored`.
+    stored: HashMap<(String, i32), i64>,
+}
+
+/// A consumer that doesn't connect to any broker, and returns a predefined sequence of messages
+/// instead. Messages are returned in the order they were provided.
+///
+/// By default all the messages are returned. Once `assign` is called, only messages from the
+/// assigned partitions are returned, starting from the specified offsets; `seek` can be used to
+/// move the position in a partition. Like the real consumers, a message is only returned if its
+/// offset is greater or equal than the current position of its partition. `resubscribe` assigns
+/// all the partitions of the given topics that appear in the messages, starting from the stored
+/// offsets.
+pub struct MockConsumer {
+    messages: Vec<OwnedMessage>,
+    state: Mutex<MockConsumerState>,
+}
+
+impl MockConsumer {
+    /// Creates a new `MockConsumer` that will return the provided messages.
+    pub fn new(messages: Vec<OwnedMessage>) -> MockConsumer {
+        MockConsumer {
+            messages,
+            state: Mutex::new(MockConsumerState { positions: HashMap::new(), assigned: false, stored: HashMap::new() }),
+        }
+    }
+
+    /// Returns the next available message, or `None` if all the messages have been consumed.
+    /// The timeout is ignored.
+    pub fn poll(&self, _timeout_ms: i32) -> Option<KafkaResult<OwnedMessage>> {
+        let mut state = self.state.lock().unwrap();
+        let assigned = state.assigned;
+        let next = self.messages.iter().find(|m| {
+            match state.positions.get(&(m.topic().into_owned(), m.partition())) {
+                Some(&position) => m.offset() >= position,
+                None => !assigned,
+            }
+        });
+        next.map(|message| {
+            state.positions.insert((message.topic().into_owned(), message.partition()), message.offset() + 1);
+            Ok(message.clone())
+        })
+    }
+
+    /// Returns a stream of the remaining messages. The stream terminates once all the messages
+    /// have been consumed.
+    pub fn start(&self) -> MockMessageStream {
+        MockMessageStream { consumer: self }
+    }
+
+    /// Restricts consumption to the partitions in the list, starting from the offsets specified.
+    pub fn assign(&self, assignment: &TopicPartitionList) -> KafkaResult<()> {
+        let mut state = self.state.lock().unwrap();
+        let mut positions = HashMap::new();
+        for elem in assignment.elements() {
+            let position = self.resolve_offset(&state, &elem.topic(), elem.partition(), elem.offset());
+            positions.insert((elem.topic().into_owned(), elem.partition()), position);
+        }
+        state.positions = positions;
+        state.assigned = true;
+        Ok(())
+    }
+
+    /// Assigns all the partitions of the given topics that appear in the messages, starting from
+    /// the stored offsets, and returns the new assignment. Topics starting with `^` are not
+    /// treated as patterns. The timeout is ignored.
+    pub fn resubscribe(&self, topics: &[&str], _timeout_ms: i32) -> KafkaResult<TopicPartitionList> {
+        let mut assignment = TopicPartitionList::new();
+        for (topic, partition) in self.partitions() {
+            if topics.contains(&topic.as_str()) {
+                assignment.add_partition_offset(&topic, partition, Offset::Stored);
+            }
+        }
+        self.assign(&assignment)?;
+        self.wait_for_assignment(0)
+    }
+
+    /// Returns the current assignment. If `assign` has never been called, all the partitions that
+    /// appear in the messages are returned. The offsets are `Offset::Invalid`, like in the
+    /// assignment returned by the real consumers. The timeout is ignored.
+    pub fn wait_for_assignment(&self, _timeout_ms: i32) -> KafkaResult<TopicPartitionList> {
+        let state = self.state.lock().unwrap();
+        let mut assignment = TopicPartitionList::new();
+        for (topic, partition) in self.partitions() {
+            if !state.assigned || state.positions.contains_key(&(topic.clone(), partition)) {
+                assignment.add_partition_offset(&topic, partition, Offset::Invalid);
+            }
+        }
+        Ok(assignment)
+    }
+
+    /// Stores the offsets of the provided list, to be used when a partition is assigned with
+    /// `Offset::Stored`. Only actual offsets are stored. Returns a copy of the list.
+    pub fn store_offsets(&self, offsets: &TopicPartitionList) -> KafkaResult<TopicPartitionList> {
+        let mut state = self.state.lock().unwrap();
+        for elem in offsets.elements() {
+            if let Offset::Offset(offset) = elem.offset() {
+                state.stored.insert((elem.topic().into_owned(), elem.partition()), offset);
+            }
+        }
+        Ok(offsets.clone())
+    }
+
+    /// Stores the offset following the highest offset of each partition in the batch. See
+    /// `Consumer::store_message_offsets`.
+    pub fn store_message_offsets<M: Message>(&self, messages: &[M]) -> KafkaResult<TopicPartitionList> {
+        let mut max_offsets = HashMap::new();
+        for message in messages {
+            let max_offset = max_offsets.entry((message.topic(), message.partition())).or_insert(message.offset());
+            if message.offset() > *max_offset {
+                *max_offset = message.offset();
+            }
+        }
+        let mut offsets = TopicPartitionList::with_capacity(max_offsets.len());
+        for ((topic, partition), offset) in max_offsets {
+            offsets.add_partition_offset(&topic, partition, Offset::Offset(offset + 1));
+        }
+        self.store_offsets(&offsets)
+    }
+
+    /// Moves the position of the given partition to the specified offset. The timeout is ignored.
+    pub fn seek(&self, topic: &str, partition: i32, offset: Offset, _timeout_ms: i32) -> KafkaResult<()> {
+        let mut state = self.state.lock().unwrap();
+        let position = self.resolve_offset(&state, topic, partition, offset);
+        if state.assigned && !state.positions.contains_key(&(topic.to_owned(), partition)) {
+            return Err(KafkaError::Seek(format!("Partition {}/{} is not assigned", topic, partition)));
+        }
+        state.positions.insert((topic.to_owned(), partition), position);
+        Ok(())
+    }
+
+    // Partitions without a stored offset are consumed from the beginning.
+    fn resolve_offset(&self, state: &MockConsumerState, topic: &str, partition: i32, offset: Offset) -> i64 {
+        match offset {
+            Offset::Offset(n) => n,
+            Offset::End => self.end_offset(topic, partition),
+            Offset::OffsetTail(n) => self.end_offset(topic, partition) - n,
+            Offset::Stored => state.stored.get(&(topic.to_owned(), partition)).cloned().unwrap_or(i64::min_value()),
+            Offset::Beginning | Offset::Invalid => i64::min_value(),
+        }
+    }
+
+    // Partitions that appear in the messages, in order of first appearance.
+    fn partitions(&self) -> Vec<(String, i32)> {
+        let mut partitions = Vec::new();
+        for message in &self.messages {
+            let partition = (message.topic().into_owned(), message.partition());
+            if !partitions.contains(&partition) {
+                partitions.push(partition);
+            }
+        }
+        partitions
+    }
+
+    fn end_offset(&self, topic: &str, partition: i32) -> i64 {
+        self.messages.iter()
+            .filter(|m| m.topic() == topic && m.partition() == partition)
+            .map(|m| m.offset() + 1)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// A stream over the messages of a `MockConsumer`. Like the `MessageStream`, it yields
+/// `KafkaResult`s; the stream terminates once all the messages have been consumed.
+pub struct MockMessageStream<'a> {
+    consumer: &'a MockConsumer,
+}
+
+impl<'a> Stream for MockMessageStream<'a> {
+    type Item = KafkaResult<OwnedMessage>;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        Ok(Async::Ready(self.consumer.poll(0)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use message::Timestamp;
+
+    fn message(partition: i32, offset: i64) -> OwnedMessage {
+        OwnedMessage::new(Some(format!("{}:{}", partition, offset).into_bytes()), None,
+                          "topic".to_owned(), Timestamp::NotAvailable, partition, offset)
+    }
+
+    fn offsets(messages: Vec<OwnedMessage>) -> Vec<(i32, i64)> {
+        messages.iter().map(|m| (m.partition(), m.offset())).collect()
+    }
+
+    #[test]
+    fn test_mock_consumer_stream() {
+        let consumer = MockConsumer::new(vec![message(0, 0), message(1, 0), message(0, 1)]);
+        let messages = consumer.start().wait().map(|r| r.unwrap().unwrap()).collect::<Vec<_>>();
+        assert_eq!(offsets(messages), vec![(0, 0), (1, 0), (0, 1)]);
+        assert!(consumer.poll(0).is_none());
+    }
+
+    #[test]
+    fn test_mock_consumer_assign_seek() {
+        let consumer = MockConsumer::new(vec![message(0, 0), message(1, 0), message(0, 1), message(1, 1)]);
+        let mut tpl = TopicPartitionList::new();
+        tpl.add_partition_offset("topic", 1, Offset::Offset(1));
+        consumer.assign(&tpl).unwrap();
+        assert!(consumer.seek("topic", 0, Offset::Beginning, 0).is_err());
+
+        let messages = consumer.start().wait().map(|r| r.unwrap().unwrap()).collect::<Vec<_>>();
+        assert_eq!(offsets(messages), vec![(1, 1)]);
+
+        consumer.seek("topic", 1, Offset::Beginning, 0).unwrap();
+        assert_eq!(consumer.poll(0).unwrap().unwrap().offset(), 0);
+        consumer.seek("topic", 1, Offset::End, 0).unwrap();
+        assert!(consumer.poll(0).is_none());
+    }
+
+    #[test]
+    fn test_mock_consumer_store_resubscribe() {
+        let consumer = MockConsumer::new(vec![message(0, 0), message(1, 0), message(0, 1), message(1, 1)]);
+        assert_eq!(consumer.wait_for_assignment(0).unwrap().count(), 2);
+
+        let first = consumer.poll(0).unwrap().unwrap();
+        consumer.store_message_offsets(&[first]).unwrap();
+        let assignment = consumer.resubscribe(&["topic"], 0).unwrap();
+        assert_eq!(assignment.count(), 2);
+        assert_eq!(assignment.find_partition("topic", 0).unwrap().offset(), Offset::Invalid);
+
+        // Partition 0 restarts from the stored offset, partition 1 from the beginning.
+        let messages = consumer.start().wait().map(|r| r.unwrap().unwrap()).collect::<Vec<_>>();
+        assert_eq!(offsets(messages), vec![(1, 0), (0, 1), (1, 1)]);
+
+        assert_eq!(consumer.resubscribe(&["other"], 0).unwrap().count(), 0);
+        assert!(consumer.poll(0).is_none());
+    }
+}
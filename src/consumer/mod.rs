@@ -1,24 +1,31 @@
 //! Base trait and common functionality for all consumers.
 pub mod base_consumer;
+pub mod group_offsets;
+pub mod mock_consumer;
 pub mod stream_consumer;
 
 // Re-export
 pub use self::base_consumer::BaseConsumer;
-pub use self::stream_consumer::{MessageStream, StreamConsumer};
+#[cfg(unix)]
+pub use self::base_consumer::BaseMessageStream;
+pub use self::group_offsets::{commit_offsets_for_group, committed_offsets_for_group};
+pub use self::mock_consumer::{MockConsumer, MockMessageStream};
+pub use self::stream_consumer::{GroupListFuture, MessageStream, StreamConsumer};
 
 use rdsys;
 use rdsys::types::*;
 
-use client::{Context, NativeClient};
+use client::{Client, Context, NativeClient};
 use error::KafkaResult;
 use groups::GroupList;
-use message::BorrowedMessage;
+use message::{BorrowedMessage, Message};
 use metadata::Metadata;
 use util::cstr_to_owned;
 
+use std::collections::HashMap;
 use std::ptr;
 
-use topic_partition_list::TopicPartitionList;
+use topic_partition_list::{Offset, TopicPartitionList};
 
 /// Rebalance information.
 #[derive(Clone, Debug)]
@@ -31,9 +38,9 @@ pub enum Rebalance<'a> {
 /// Consumer specific Context. This user-defined object can be used to provide custom callbacks to
 /// consumer events. Refer to the list of methods to check which callbacks can be specified.
 pub trait ConsumerContext: Context {
-    /// Implements the default rebalancing strategy and calls the `pre_rebalance` and
-    /// `post_rebalance` methods. If this method is overridden, it will be responsibility
-    /// of the user to call them if needed.
+    /// Implements the default rebalancing strategy and calls the `override_assignment`,
+    /// `pre_rebalance` and `post_rebalance` methods. If this method is overridden, it will be
+    /// responsibility of the user to call them if needed.
     fn rebalance(
         &self,
         native_client: &NativeClient,
@@ -41,9 +48,15 @@ pub trait ConsumerContext: Context {
         tpl: &TopicPartitionList,
     ) {
 
+        let overridden_assignment = match err {
+            RDKafkaRespErr::RD_KAFKA_RESP_ERR__ASSIGN_PARTITIONS => self.override_assignment(tpl),
+            _ => None,
+        };
+        let assignment = overridden_assignment.as_ref().unwrap_or(tpl);
+
         let rebalance = match err {
             RDKafkaRespErr::RD_KAFKA_RESP_ERR__ASSIGN_PARTITIONS => {
-                Rebalance::Assign(tpl)
+                Rebalance::Assign(assignment)
             }
             RDKafkaRespErr::RD_KAFKA_RESP_ERR__REVOKE_PARTITIONS => Rebalance::Revoke,
             _ => {
@@ -61,7 +74,7 @@ pub trait ConsumerContext: Context {
         unsafe {
             match err {
                 RDKafkaRespErr::RD_KAFKA_RESP_ERR__ASSIGN_PARTITIONS => {
-                    rdsys::rd_kafka_assign(native_client.ptr(), tpl.ptr());
+                    rdsys::rd_kafka_assign(native_client.ptr(), assignment.ptr());
                 },
                 _ => {  // Also for RD_KAFKA_RESP_ERR__REVOKE_PARTITIONS
                     rdsys::rd_kafka_assign(native_client.ptr(), ptr::null());
@@ -72,6 +85,20 @@ pub trait ConsumerContext: Context {
         self.post_rebalance(&rebalance);
     }
 
+    /// Returns the partitions that should be assigned to the consumer instead of the ones
+    /// proposed by the group, or `None` to accept the proposed assignment (the default). The
+    /// returned list is passed to `pre_rebalance` and `post_rebalance`, and is assigned once:
+    /// the proposed assignment is not applied.
+    ///
+    /// This is an advanced feature. The returned list must contain all the partitions the
+    /// consumer intends to consume, with their starting offsets if needed. The group still
+    /// considers the rejected partitions as assigned to this consumer, so they won't be consumed
+    /// by any other member until the next rebalance.
+    #[allow(unused_variables)]
+    fn override_assignment(&self, proposed: &TopicPartitionList) -> Option<TopicPartitionList> {
+        None
+    }
+
     /// Pre-rebalance callback. This method will run before the rebalance and should
     /// terminate its execution quickly.
     #[allow(unused_variables)]
@@ -111,20 +138,95 @@ pub trait Consumer<C: ConsumerContext> {
 
     // Default implementations
 
-    /// Subscribe the consumer to a list of topics.
+    /// Returns a reference to the underlying client.
+    fn client(&self) -> &Client<C> {
+        self.get_base_consumer().client()
+    }
+
+    /// Subscribe the consumer to a list of topics. Topic names starting with `^` are regular
+    /// expressions: the consumer will be subscribed to all the matching topics. Subscription
+    /// requires the consumer to be created with a `group.id`: otherwise a `KafkaError::ClientConfig`
+    /// error is returned.
     fn subscribe(&self, topics: &[&str]) -> KafkaResult<()> {
         self.get_base_consumer().subscribe(topics)
     }
 
+    /// Subscribes the consumer to a list of topics, like `subscribe`, after checking that all the
+    /// topics exist in the cluster. The metadata of the whole cluster is fetched, waiting up to
+    /// `timeout_ms`, so that the check doesn't trigger the automatic creation of the topics. If some
+    /// topics don't exist, a `KafkaError::Subscription` error listing them is returned, and the
+    /// subscription is not changed. Regular expressions are not checked. Use `subscribe` for topics
+    /// that are expected to be created later.
+    fn subscribe_checked(&self, topics: &[&str], timeout_ms: i32) -> KafkaResult<()> {
+        self.get_base_consumer().subscribe_checked(topics, timeout_ms)
+    }
+
     fn unsubscribe(&self) {
         self.get_base_consumer().unsubscribe();
     }
 
+    /// Subscribes the consumer to the topics contained in the provided list, and starts consuming
+    /// each partition from the offset specified in the list once the partition is assigned to the
+    /// consumer, instead of the committed offset. The offsets are only applied to the first
+    /// assignment of each partition. This can be used to resume group consumption from offsets
+    /// stored in an external checkpoint store.
+    fn subscribe_with_offsets(&self, offsets: &TopicPartitionList) -> KafkaResult<()> {
+        self.get_base_consumer().subscribe_with_offsets(offsets)
+    }
+
+    /// Replaces the current subscription with the provided list of topics and waits until the
+    /// resulting rebalance has completed, returning the new assignment. Rebalances are executed
+    /// by the thread polling the consumer: when using a `BaseConsumer`, `poll` should be called by
+    /// a different thread while waiting. The new assignment might be empty, or the same as the
+    /// previous one. An error is returned if the rebalance doesn't complete within the timeout;
+    /// set the timeout to -1 to wait indefinitely.
+    fn resubscribe(&self, topics: &[&str], timeout_ms: i32) -> KafkaResult<TopicPartitionList> {
+        self.get_base_consumer().resubscribe(topics, timeout_ms)
+    }
+
     /// Manually assign topics and partitions to the consumer.
     fn assign(&self, assignment: &TopicPartitionList) -> KafkaResult<()> {
         self.get_base_consumer().assign(assignment)
     }
 
+    /// Pauses consumption for the provided list of partitions.
+    fn pause(&self, partitions: &TopicPartitionList) -> KafkaResult<()> {
+        self.get_base_consumer().pause(partitions)
+    }
+
+    /// Resumes consumption for the provided list of partitions.
+    fn resume(&self, partitions: &TopicPartitionList) -> KafkaResult<()> {
+        self.get_base_consumer().resume(partitions)
+    }
+
+    /// Seeks to `offset` for the specified topic and partition. The partition must be currently
+    /// assigned to the consumer. The next message returned by the consumer for that partition
+    /// will be the one at the specified offset.
+    fn seek(&self, topic: &str, partition: i32, offset: Offset, timeout_ms: i32) -> KafkaResult<()> {
+        self.get_base_consumer().seek(topic, partition, offset, timeout_ms)
+    }
+
+    /// Seeks every partition in the list to the offset specified in the list. Returns a copy of
+    /// the list where the error of each partition is set, for example if the partition is not
+    /// assigned to the consumer. The partitions are sought one by one, and the timeout applies
+    /// to each of them.
+    fn seek_partitions(&self, partitions: &TopicPartitionList, timeout_ms: i32) -> KafkaResult<TopicPartitionList> {
+        self.get_base_consumer().seek_partitions(partitions, timeout_ms)
+    }
+
+    /// Pauses the consumption of the specified topic and partition and seeks it to `offset`, so
+    /// that once the partition is resumed the consumer will restart from `offset`. This can be
+    /// used to stop consuming a partition after a processing error, and to consume it again from
+    /// the last successfully processed message once the error is solved.
+    ///
+    /// Note that with automatic commit, the offsets of the messages returned before the failure
+    /// might be committed regardless of their processing status. To avoid skipping messages,
+    /// `enable.auto.offset.store` should be set to `false` and offsets should be stored with
+    /// `store_offset` only after the message has been processed.
+    fn pause_and_seek(&self, topic: &str, partition: i32, offset: Offset, timeout_ms: i32) -> KafkaResult<()> {
+        self.get_base_consumer().pause_and_seek(topic, partition, offset, timeout_ms)
+    }
+
     /// Commits the offset of the specified message. The commit can be sync (blocking), or async.
     /// Notice that when a specific offset is committed, all the previous offsets are considered
     /// committed as well. Use this method only if you are processing messages in order.
@@ -144,17 +246,52 @@ pub trait Consumer<C: ConsumerContext> {
         self.get_base_consumer().commit_message(message, mode)
     }
 
+    /// Commits the offset following the one of the provided message, only for the topic and
+    /// partition of the message. Differently from `commit_message`, it also accepts messages that
+    /// have been detached from the consumer, such as an `OwnedMessage`.
+    fn commit_message_offset<M: Message>(&self, message: &M, mode: CommitMode) -> KafkaResult<()>
+            where Self: Sized {
+        self.get_base_consumer().commit_message_offset(message, mode)
+    }
+
     /// Store offset for this message to be used on the next (auto)commit.
     /// When using this `enable.auto.offset.store` should be set to `false` in the config.
     fn store_offset(&self, message: &BorrowedMessage) -> KafkaResult<()> {
         self.get_base_consumer().store_offset(message)
     }
 
-    /// Returns the current topic subscription.
+    /// Stores the offsets of the provided list, to be used on the next (auto)commit. Returns a
+    /// copy of the list where the error of each partition is set: the offsets of partitions
+    /// unknown to the consumer are not stored, and report `UnknownPartition`. An error is
+    /// returned instead if no offset could be stored: `InvalidArgument` if
+    /// `enable.auto.offset.store` is not set to `false`, `UnknownPartition` if none of the
+    /// partitions is known.
+    fn store_offsets(&self, offsets: &TopicPartitionList) -> KafkaResult<TopicPartitionList> {
+        self.get_base_consumer().store_offsets(offsets)
+    }
+
+    /// Stores the offsets of a batch of processed messages, to be used on the next (auto)commit.
+    /// For each topic and partition, the offset following the highest offset among the messages
+    /// is stored, regardless of the order of the messages. The result is the same as for
+    /// `store_offsets`.
+    fn store_message_offsets<M: Message>(&self, messages: &[M]) -> KafkaResult<TopicPartitionList>
+            where Self: Sized {
+        self.get_base_consumer().store_message_offsets(messages)
+    }
+
+    /// Returns the current topic subscription, as provided to `subscribe`: regular expressions
+    /// are returned as they are, and not expanded to the matching topics. Use `assignment` to
+    /// find out which partitions are currently consumed.
     fn subscription(&self) -> KafkaResult<TopicPartitionList> {
         self.get_base_consumer().subscription()
     }
 
+    /// Returns the regular expressions in the current subscription, i.e. the subscribed topic
+    /// names starting with `^`.
+    fn subscription_patterns(&self) -> KafkaResult<Vec<String>> {
+        self.get_base_consumer().subscription_patterns()
+    }
+
     /// Returns the current partition assignment.
     fn assignment(&self) -> KafkaResult<TopicPartitionList> {
         self.get_base_consumer().assignment()
@@ -171,11 +308,31 @@ pub trait Consumer<C: ConsumerContext> {
             .offsets_for_timestamp(timestamp, timeout_ms)
     }
 
+    /// Waits until the consumer has been assigned at least one partition after a subscription,
+    /// and returns the assignment. The consumer is polled while waiting, to serve the rebalance
+    /// callback: the new partitions are paused until the assignment is returned, so that no
+    /// message is consumed (or automatically stored) in the meantime. If no partition is assigned
+    /// within `timeout_ms` milliseconds, the empty assignment is returned. Set the timeout to -1
+    /// to wait indefinitely.
+    fn wait_for_assignment(&self, timeout_ms: i32) -> KafkaResult<TopicPartitionList> {
+        self.get_base_consumer().wait_for_assignment(timeout_ms)
+    }
+
     /// Retrieve current positions (offsets) for topics and partitions.
     fn position(&self) -> KafkaResult<TopicPartitionList> {
         self.get_base_consumer().position()
     }
 
+    /// Returns the offsets the consumer will resume from for each assigned partition. This is
+    /// the current position, or the committed offset for partitions whose position is not known
+    /// yet, for instance right after a rebalance, before any message has been fetched. The
+    /// committed offsets are only fetched, waiting up to `timeout_ms`, if some position is
+    /// unknown. Partitions with neither a position nor a committed offset are returned with
+    /// `Offset::Invalid`.
+    fn effective_position(&self, timeout_ms: i32) -> KafkaResult<TopicPartitionList> {
+        self.get_base_consumer().effective_position(timeout_ms)
+    }
+
     /// Returns the metadata information for the specified topic, or for all topics in the cluster
     /// if no topic is specified.
     fn fetch_metadata(&self, topic: Option<&str>, timeout_ms: i32) -> KafkaResult<Metadata> {
@@ -183,6 +340,36 @@ pub trait Consumer<C: ConsumerContext> {
             .fetch_metadata(topic, timeout_ms)
     }
 
+    /// Forces a refresh of the metadata of the topics known to the consumer, such as the
+    /// subscribed ones. If the number of partitions of a subscribed topic changed, the new
+    /// partitions will be picked up by the next rebalance, without waiting for the periodic
+    /// metadata refresh (configured with `topic.metadata.refresh.interval.ms`).
+    fn refresh_metadata(&self, timeout_ms: i32) -> KafkaResult<()> {
+        self.get_base_consumer().refresh_metadata(timeout_ms)
+    }
+
+    /// Returns true if the topic exists in the cluster, false otherwise. An error is returned only
+    /// if the metadata couldn't be fetched.
+    fn topic_exists(&self, topic: &str, timeout_ms: i32) -> KafkaResult<bool> {
+        self.get_base_consumer()
+            .topic_exists(topic, timeout_ms)
+    }
+
+    /// Returns the metadata information for the specified topics, without fetching the metadata
+    /// of the whole cluster. The returned metadata might also contain other topics already known to
+    /// the consumer, such as the subscribed ones.
+    fn fetch_metadata_for_topics(&self, topics: &[&str], timeout_ms: i32) -> KafkaResult<Metadata> {
+        self.get_base_consumer()
+            .fetch_metadata_for_topics(topics, timeout_ms)
+    }
+
+    /// Returns all the partitions of the specified topics, as a list of unassigned partitions.
+    /// This can be used to know in advance which partitions might be assigned to the consumer.
+    /// An error is returned if the metadata of any of the topics is not available.
+    fn all_partitions(&self, topics: &[&str], timeout_ms: i32) -> KafkaResult<TopicPartitionList> {
+        self.get_base_consumer().all_partitions(topics, timeout_ms)
+    }
+
     /// Returns the metadata information for all the topics in the cluster.
     fn fetch_watermarks(&self, topic: &str, partition: i32, timeout_ms: i32) -> KafkaResult<(i64, i64)> {
         self.get_base_consumer()
@@ -195,4 +382,28 @@ pub trait Consumer<C: ConsumerContext> {
         self.get_base_consumer()
             .fetch_group_list(group, timeout_ms)
     }
+
+    /// Returns the lag of every assigned partition, indexed by topic name and partition. The lag
+    /// is computed as the difference between the high watermark and the current position of the
+    /// consumer. If the position of a partition is not known yet, for instance before any message
+    /// has been fetched, the committed offset is used instead. If a partition has neither a
+    /// position nor a committed offset, the lag will be the difference between the high and the
+    /// low watermark.
+    fn fetch_lag(&self, timeout_ms: i32) -> KafkaResult<HashMap<(String, i32), i64>> {
+        self.get_base_consumer().fetch_lag(timeout_ms)
+    }
+
+    /// Returns the member id assigned to the consumer by the group coordinator, or `None` if the
+    /// consumer hasn't joined the group yet.
+    fn member_id(&self) -> Option<String> {
+        self.get_base_consumer().member_id()
+    }
+
+    /// Returns the number of events, mostly fetched messages, waiting in the consumer queue to be
+    /// returned by the consumer. A growing value means that the application is not consuming
+    /// messages as fast as they are fetched. More detailed per partition information is available
+    /// in the statistics (`fetchq_cnt` and `fetchq_size`).
+    fn fetch_queue_len(&self) -> usize {
+        self.get_base_consumer().fetch_queue_len()
+    }
 }
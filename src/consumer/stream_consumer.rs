@@ -1,5 +1,6 @@
 //! Stream-based consumer implementation.
-use futures::{Future, Poll, Sink, Stream};
+use futures::{self, Async, AsyncSink, Canceled, Future, Oneshot, Poll, Stream};
+use futures::executor::{self, Notify, NotifyHandle, Spawn};
 use futures::sync::mpsc;
 use rdsys::types::*;
 use rdsys;
@@ -8,6 +9,7 @@ use config::{FromClientConfig, FromClientConfigAndContext, ClientConfig};
 use consumer::base_consumer::BaseConsumer;
 use consumer::{Consumer, ConsumerContext, EmptyConsumerContext};
 use error::{KafkaError, KafkaResult};
+use groups::GroupList;
 use message::BorrowedMessage;
 use util::duration_to_millis;
 
@@ -94,37 +96,102 @@ impl<'a, C: ConsumerContext + 'a> Stream for MessageStream<'a, C> {
     }
 }
 
+/// A `Future` wrapping the result of a group list fetch executed in the background. See
+/// `StreamConsumer::fetch_group_list_async`.
+pub struct GroupListFuture {
+    rx: Oneshot<KafkaResult<GroupList>>,
+}
+
+impl Future for GroupListFuture {
+    type Item = GroupList;
+    type Error = KafkaError;
+
+    fn poll(&mut self) -> Poll<GroupList, KafkaError> {
+        match self.rx.poll() {
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Ok(Async::Ready(Ok(group_list))) => Ok(Async::Ready(group_list)),
+            Ok(Async::Ready(Err(e))) => Err(e),
+            Err(Canceled) => Err(KafkaError::FutureCanceled),
+        }
+    }
+}
+
+/// Wakes up the polling thread when the stream is ready to receive a new message.
+struct ThreadNotify {
+    thread: thread::Thread,
+}
+
+impl Notify for ThreadNotify {
+    fn notify(&self, _id: usize) {
+        self.thread.unpark();
+    }
+}
+
+/// Sends an item to the stream, parking the polling thread while the stream is full. The thread
+/// is unparked either by the stream, when it's ready to receive the item, or by
+/// `StreamConsumer::stop`. Returns false if the item couldn't be sent because the consumer is
+/// being stopped or the stream has been dropped.
+fn send_to_stream(
+    sender: &mut Spawn<mpsc::Sender<Option<PolledMessagePtr>>>,
+    mut item: Option<PolledMessagePtr>,
+    notify: &NotifyHandle,
+    should_stop: &AtomicBool,
+) -> bool {
+    loop {
+        match sender.start_send_notify(item, notify, 0) {
+            Ok(AsyncSink::Ready) => return true,
+            Ok(AsyncSink::NotReady(returned_item)) => {
+                if should_stop.load(Ordering::Relaxed) {
+                    return false;
+                }
+                item = returned_item;
+                thread::park();
+            },
+            Err(e) => {
+                debug!("Sender not available: {:?}", e);
+                return false;
+            }
+        }
+    }
+}
+
 /// Internal consumer loop. This is the main body of the thread that will drive the stream consumer.
 /// If `send_none` is true, the loop will send a None into the sender every time the poll times out.
+/// Partition EOF events are only sent if `partition_eof` is true.
 fn poll_loop<C: ConsumerContext>(
     consumer: Arc<BaseConsumer<C>>,
     sender: mpsc::Sender<Option<PolledMessagePtr>>,
     should_stop: Arc<AtomicBool>,
+    partition_eof: Arc<AtomicBool>,
     poll_interval: Duration,
     send_none: bool,
 ) {
     trace!("Polling thread loop started");
-    let mut curr_sender = sender;
+    let mut sender = executor::spawn(sender);
+    let notify = NotifyHandle::from(Arc::new(ThreadNotify { thread: thread::current() }));
     let poll_interval_ms = duration_to_millis(poll_interval) as i32;
     while !should_stop.load(Ordering::Relaxed) {
         trace!("Polling base consumer");
-        let future_sender = match consumer.poll_raw(poll_interval_ms) {
+        let item = match consumer.poll_raw(poll_interval_ms) {
             None => {
                 if send_none {
-                    curr_sender.send(None)
+                    None
                 } else {
                     continue // TODO: check stream closed
                 }
             },
-            Some(m_ptr) => curr_sender.send(Some(PolledMessagePtr::new(m_ptr))),
-        };
-        match future_sender.wait() {
-            Ok(new_sender) => curr_sender = new_sender,
-            Err(e) => {
-                debug!("Sender not available: {:?}", e);
-                break;
-            }
+            Some(m_ptr) => {
+                let polled_ptr = PolledMessagePtr::new(m_ptr);
+                let is_eof = unsafe { (*m_ptr).err } == RDKafkaRespErr::RD_KAFKA_RESP_ERR__PARTITION_EOF;
+                if is_eof && !partition_eof.load(Ordering::Relaxed) {
+                    continue // The message is destroyed by the drop of the PolledMessagePtr
+                }
+                Some(polled_ptr)
+            },
         };
+        if !send_to_stream(&mut sender, item, &notify, &should_stop) {
+            break;
+        }
     }
     trace!("Polling thread loop terminated");
 }
@@ -140,6 +207,7 @@ fn poll_loop<C: ConsumerContext>(
 pub struct StreamConsumer<C: ConsumerContext + 'static> {
     consumer: Arc<BaseConsumer<C>>,
     should_stop: Arc<AtomicBool>,
+    partition_eof: Arc<AtomicBool>,
     handle: Cell<Option<JoinHandle<()>>>,
 }
 
@@ -161,6 +229,7 @@ impl<C: ConsumerContext> FromClientConfigAndContext<C> for StreamConsumer<C> {
         let stream_consumer = StreamConsumer {
             consumer: Arc::new(BaseConsumer::from_config_and_context(config, context)?),
             should_stop: Arc::new(AtomicBool::new(false)),
+            partition_eof: Arc::new(AtomicBool::new(false)),
             handle: Cell::new(None),
         };
         Ok(stream_consumer)
@@ -178,26 +247,72 @@ impl<C: ConsumerContext> StreamConsumer<C> {
     /// `no_message_error` is set to true, it will return an error of type
     /// `KafkaError::NoMessageReceived` every time the poll interval is reached and no message has
     /// been received.
+    ///
+    /// If the consumer is already running, the current polling thread is stopped first, and the
+    /// stream previously returned will terminate. The subscription and the assignment of the
+    /// consumer are not affected, so this method can be used to restart consumption after a call
+    /// to `stop`.
     pub fn start_with(&self, poll_interval: Duration, no_message_error: bool) -> MessageStream<C> {
-        // TODO: verify called once
+        self.stop();
+        self.should_stop.store(false, Ordering::Relaxed);
         let (sender, receiver) = mpsc::channel(0);
         let consumer = self.consumer.clone();
         let should_stop = self.should_stop.clone();
+        let partition_eof = self.partition_eof.clone();
         let handle = thread::Builder::new()
-            .name("poll".to_string())
+            .name(format!("rdkafka-poll-{}", self.client().name()))
             .spawn(move || {
-                poll_loop(consumer, sender, should_stop, poll_interval, no_message_error);
+                poll_loop(consumer, sender, should_stop, partition_eof, poll_interval, no_message_error);
             })
             .expect("Failed to start polling thread");
         self.handle.set(Some(handle));
         MessageStream::new(self, receiver)
     }
 
+    /// Enables or disables the partition EOF events in the stream. When enabled, a
+    /// `KafkaError::PartitionEOF` error is returned by the stream every time the consumer reaches
+    /// the end of a partition, which can be used to detect that the consumer has caught up. The
+    /// events are generated by librdkafka only if `enable.partition.eof` is set to `true` (the
+    /// default), and are discarded by the stream unless enabled with this method. The setting can
+    /// be changed while the consumer is running.
+    pub fn enable_partition_eof(&self, enable: bool) {
+        self.partition_eof.store(enable, Ordering::Relaxed);
+    }
+
+    /// Returns a future that will be completed with the group membership information for the given
+    /// group, or for all groups if no group is specified. The same as `fetch_group_list`, but the
+    /// request is executed in a separate thread, so that the caller is not blocked while waiting
+    /// for the response of the cluster.
+    pub fn fetch_group_list_async(&self, group: Option<&str>, timeout_ms: i32) -> GroupListFuture {
+        let (tx, rx) = futures::oneshot();
+        let consumer = self.consumer.clone();
+        let group = group.map(|group| group.to_owned());
+        thread::Builder::new()
+            .name(format!("rdkafka-group-list-{}", self.client().name()))
+            .spawn(move || {
+                let result = consumer.fetch_group_list(group.as_ref().map(|group| group.as_str()), timeout_ms);
+                // The receiver might have been dropped: the result is not needed anymore.
+                let _ = tx.send(result);
+            })
+            .expect("Failed to start group list thread");
+        GroupListFuture { rx }
+    }
+
     /// Stops the StreamConsumer, blocking the caller until the internal consumer has been stopped.
+    /// The consumer remains subscribed and keeps its group membership, and can be started again
+    /// with `start` or `start_with`. Calling `stop` on a consumer that is not running has no
+    /// effect. The stream doesn't need to be consumed for the consumer to stop: the polling
+    /// thread stops waiting for the stream to be ready, and the stream terminates once the
+    /// messages it already contains have been received.
+    ///
+    /// Note that a message that has already been polled from the internal consumer, but not yet
+    /// sent to the stream, will be discarded.
     pub fn stop(&self) {
         if let Some(handle) = self.handle.take() {
             trace!("Stopping polling");
             self.should_stop.store(true, Ordering::Relaxed);
+            // Wake up the polling thread, in case it's waiting for the stream to be ready.
+            handle.thread().unpark();
             match handle.join() {
                 Ok(()) => trace!("Polling stopped"),
                 Err(e) => warn!("Failure while terminating thread: {:?}", e),
@@ -210,8 +325,29 @@ impl<C: ConsumerContext> Drop for StreamConsumer<C> {
     fn drop(&mut self) {
         trace!("Destroy StreamConsumer");
         // The polling thread must be fully stopped before we can proceed with the actual drop,
-        // otherwise it might consume from a destroyed consumer.
+        // otherwise it might consume from a destroyed consumer. Once the thread is joined, its
+        // reference to the base consumer is released, and the base consumer (together with its
+        // native client and context) will be destroyed when the fields are dropped.
         self.stop();
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::ClientConfig;
+
+    // Stopping the consumer shouldn't block if the stream is full and it's not being consumed.
+    #[test]
+    fn test_stream_consumer_stop_undrained_stream() {
+        let consumer = ClientConfig::new()
+            .set("group.id", "rdkafka_test_stream_consumer_stop")
+            .create::<StreamConsumer<_>>()
+            .unwrap();
+        // `NoMessageReceived` notifications fill the stream even without a broker.
+        let _stream = consumer.start_with(Duration::from_millis(10), true);
+        thread::sleep(Duration::from_millis(200));
+        consumer.stop();
+    }
+}
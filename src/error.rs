@@ -4,7 +4,7 @@ use rdsys::types::*;
 use std::{error, ffi, fmt};
 
 // Re-export rdkafka error
-pub use rdsys::types::RDKafkaError;
+pub use rdsys::types::{RDKafkaError, RDKafkaRespErr};
 
 /// Kafka result.
 pub type KafkaResult<T> = Result<T, KafkaError>;
@@ -36,22 +36,47 @@ pub enum KafkaError {
     ClientCreation(String),
     ConsumerCommit(RDKafkaError),
     ConsumerCreation(String),
+    Flush(RDKafkaError),
     FutureCanceled,
     Global(RDKafkaError),
     GroupListFetch(RDKafkaError),
     MessageConsumption(RDKafkaError),
     MessageProduction(RDKafkaError),
+    MessageTooLarge(usize),
     MetadataFetch(RDKafkaError),
     NoMessageReceived,
     Nul(ffi::NulError),
     OffsetFetch(RDKafkaError),
     PartitionEOF(i32),
+    PauseResume(String),
+    Seek(String),
     SetPartitionOffset(RDKafkaError),
     StoreOffset(RDKafkaError),
     Subscription(String),
     TopicConfig(RDKafkaConfRes, String, String, String),
 }
 
+impl KafkaError {
+    /// Returns the librdkafka error code carried by the error, if any. The code can be converted
+    /// to the raw `RDKafkaRespErr` using `into`.
+    pub fn rdkafka_error_code(&self) -> Option<RDKafkaError> {
+        match *self {
+            KafkaError::ConsumerCommit(err) |
+            KafkaError::Flush(err) |
+            KafkaError::Global(err) |
+            KafkaError::GroupListFetch(err) |
+            KafkaError::MessageConsumption(err) |
+            KafkaError::MessageProduction(err) |
+            KafkaError::MetadataFetch(err) |
+            KafkaError::OffsetFetch(err) |
+            KafkaError::SetPartitionOffset(err) |
+            KafkaError::StoreOffset(err) => Some(err),
+            KafkaError::PartitionEOF(_) => Some(RDKafkaError::PartitionEOF),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Debug for KafkaError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -59,16 +84,20 @@ impl fmt::Debug for KafkaError {
             KafkaError::ClientCreation(ref err) => write!(f, "KafkaError (Client creation error: {})", err),
             KafkaError::ConsumerCommit(err) => write!(f, "KafkaError (Consumer commit error: {})", err),
             KafkaError::ConsumerCreation(ref err) => write!(f, "KafkaError (Consumer creation error: {})", err),
+            KafkaError::Flush(err) => write!(f, "KafkaError (Flush error: {})", err),
             KafkaError::FutureCanceled => write!(f, "Future canceled"),
             KafkaError::Global(err) => write!(f, "KafkaError (Global error: {})", err),
             KafkaError::GroupListFetch(err) => write!(f, "KafkaError (Group list fetch error: {})", err),
             KafkaError::MessageConsumption(err) => write!(f, "KafkaError (Message consumption error: {})", err),
             KafkaError::MessageProduction(err) => write!(f, "KafkaError (Message production error: {})", err),
+            KafkaError::MessageTooLarge(max) => write!(f, "KafkaError (Message too large: maximum message size is {} bytes)", max),
             KafkaError::MetadataFetch(err) => write!(f, "KafkaError (Metadata fetch error: {})", err),
             KafkaError::NoMessageReceived => write!(f, "No message received within the given poll interval"),
             KafkaError::Nul(_) => write!(f, "FFI null error"),
             KafkaError::OffsetFetch(err) => write!(f, "KafkaError (Offset fetch error: {})", err),
             KafkaError::PartitionEOF(part_n) => write!(f, "KafkaError (Partition EOF: {})", part_n),
+            KafkaError::PauseResume(ref err) => write!(f, "KafkaError (Pause/resume error: {})", err),
+            KafkaError::Seek(ref err) => write!(f, "KafkaError (Seek error: {})", err),
             KafkaError::SetPartitionOffset(err) => write!(f, "KafkaError (Set partition offset error: {})", err),
             KafkaError::StoreOffset(err) => write!(f, "KafkaError (Store offset error: {})", err),
             KafkaError::Subscription(ref err) => write!(f, "KafkaError (Subscription error: {})", err),
@@ -84,16 +113,20 @@ impl fmt::Display for KafkaError {
             KafkaError::ClientCreation(ref err) => write!(f, "Client creation error: {}", err),
             KafkaError::ConsumerCommit(err) => write!(f, "Consumer commit error: {}", err),
             KafkaError::ConsumerCreation(ref err) => write!(f, "Consumer creation error: {}", err),
+            KafkaError::Flush(err) => write!(f, "Flush error: {}", err),
             KafkaError::FutureCanceled => write!(f, "Future canceled"),
             KafkaError::Global(err) => write!(f, "Global error: {}", err),
             KafkaError::GroupListFetch(err) => write!(f, "Group list fetch error: {}", err),
             KafkaError::MessageConsumption(err) => write!(f, "Message consumption error: {}", err),
             KafkaError::MessageProduction(err) => write!(f, "Message production error: {}", err),
+            KafkaError::MessageTooLarge(max) => write!(f, "Message too large: maximum message size is {} bytes", max),
             KafkaError::MetadataFetch(err) => write!(f, "Meta data fetch error: {}", err),
             KafkaError::NoMessageReceived => write!(f, "No message received within the given poll interval"),
             KafkaError::Nul(_) => write!(f, "FFI nul error"),
             KafkaError::OffsetFetch(err) => write!(f, "Offset fetch error: {}", err),
             KafkaError::PartitionEOF(part_n) => write!(f, "Partition EOF: {}", part_n),
+            KafkaError::PauseResume(ref err) => write!(f, "Pause/resume error: {}", err),
+            KafkaError::Seek(ref err) => write!(f, "Seek error: {}", err),
             KafkaError::SetPartitionOffset(err) => write!(f, "Set partition offset error: {}", err),
             KafkaError::StoreOffset(err) => write!(f, "Store offset error: {}", err),
             KafkaError::Subscription(ref err) => write!(f, "Subscription error: {}", err),
@@ -109,16 +142,20 @@ impl error::Error for KafkaError {
             KafkaError::ClientCreation(_) => "Client creation error",
             KafkaError::ConsumerCommit(_) => "Consumer commit error",
             KafkaError::ConsumerCreation(_) => "Consumer creation error",
+            KafkaError::Flush(_) => "Flush error",
             KafkaError::FutureCanceled => "Future canceled",
             KafkaError::Global(_) => "Global error",
             KafkaError::GroupListFetch(_) => "Group list fetch error",
             KafkaError::MessageConsumption(_) => "Message consumption error",
             KafkaError::MessageProduction(_) => "Message production error",
+            KafkaError::MessageTooLarge(_) => "Message too large",
             KafkaError::MetadataFetch(_) => "Meta data fetch error",
             KafkaError::NoMessageReceived => "No message received within the given poll interval",
             KafkaError::Nul(_) => "FFI nul error",
             KafkaError::OffsetFetch(_) => "Offset fetch error",
             KafkaError::PartitionEOF(_) => "Partition EOF error",
+            KafkaError::PauseResume(_) => "Pause/resume error",
+            KafkaError::Seek(_) => "Seek error",
             KafkaError::SetPartitionOffset(_) => "Set partition offset error",
             KafkaError::StoreOffset(_) => "Store offset error",
             KafkaError::Subscription(_) => "Subscription error",
@@ -133,16 +170,20 @@ impl error::Error for KafkaError {
             KafkaError::ClientCreation(_) => None,
             KafkaError::ConsumerCommit(ref err) => Some(err),
             KafkaError::ConsumerCreation(_) => None,
+            KafkaError::Flush(ref err) => Some(err),
             KafkaError::FutureCanceled => None,
             KafkaError::Global(ref err) => Some(err),
             KafkaError::GroupListFetch(ref err) => Some(err),
             KafkaError::MessageConsumption(ref err) => Some(err),
             KafkaError::MessageProduction(ref err) => Some(err),
+            KafkaError::MessageTooLarge(_) => None,
             KafkaError::MetadataFetch(ref err) => Some(err),
             KafkaError::NoMessageReceived => None,
             KafkaError::Nul(_) => None,
             KafkaError::OffsetFetch(ref err) => Some(err),
             KafkaError::PartitionEOF(_) => None,
+            KafkaError::PauseResume(_) => None,
+            KafkaError::Seek(_) => None,
             KafkaError::SetPartitionOffset(ref err) => Some(err),
             KafkaError::StoreOffset(ref err) => Some(err),
             KafkaError::Subscription(_) => None,
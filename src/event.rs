@@ -0,0 +1,348 @@
+//! Pull based consumption of librdkafka events.
+//!
+//! The producers and consumers in this crate receive the events generated by librdkafka (delivery
+//! reports, rebalances, errors, statistics and so on) through the callbacks of their context,
+//! which are called by librdkafka from inside `poll`. The `EventConsumer` uses the librdkafka
+//! event API instead: the events are enabled when the client is created, and they are returned
+//! one by one as `Event`s by `EventConsumer::poll_event`, without going through the context. This
+//! model fits better some event loop based architectures, where the application wants to decide
+//! when and where each event should be processed.
+//!
+//! Only the events listed in `Event` are delivered to the queue. The context is still used for
+//! the callbacks that don't have a corresponding event, such as `Context::throttle`.
+//!
+//! ## Consumers
+//! When the client is a consumer, the fetched messages are returned as `Event::Message` and the
+//! rebalances are returned as `Event::Rebalance`. Since rebalance events replace the rebalance
+//! callback, the application is responsible for updating the assignment with `assign` every time
+//! a rebalance event is received, otherwise no message will be consumed.
+//!
+//! ## Logs
+//! Log events are only generated if the client is configured with `log.queue` set to `true`,
+//! otherwise the log lines are passed to `Context::log` as usual.
+
+use rdsys;
+use rdsys::types::*;
+
+use std::mem;
+use std::ptr;
+
+use serde_json;
+
+use client::{Client, Context, NativeQueue, OpaqueContext};
+use config::{ClientConfig, RDKafkaLogLevel};
+use error::{IsError, KafkaError, KafkaResult};
+use message::{BorrowedMessage, OwnedMessage, ToBytes};
+use producer::base_producer::produce_copy;
+use statistics::Statistics;
+use topic_partition_list::TopicPartitionList;
+use util::{cstr_to_owned, kafka_cstr};
+
+/// The events enabled by the `EventConsumer`.
+const ENABLED_EVENTS: i32 = (rdsys::RD_KAFKA_EVENT_DR | rdsys::RD_KAFKA_EVENT_FETCH | rdsys::RD_KAFKA_EVENT_LOG
+    | rdsys::RD_KAFKA_EVENT_ERROR | rdsys::RD_KAFKA_EVENT_REBALANCE | rdsys::RD_KAFKA_EVENT_OFFSET_COMMIT
+    | rdsys::RD_KAFKA_EVENT_STATS) as i32;
+
+/// The result of the delivery of a message, as reported in a delivery report event.
+pub type EventDeliveryResult = Result<OwnedMessage, (KafkaError, OwnedMessage)>;
+
+/// A rebalance of a consumer group.
+#[derive(Debug)]
+pub enum RebalanceEvent {
+    /// The partitions have been assigned to the consumer.
+    Assign(TopicPartitionList),
+    /// The partitions have been revoked from the consumer.
+    Revoke(TopicPartitionList),
+    /// The rebalance failed.
+    Error(KafkaError),
+}
+
+/// An event generated by librdkafka.
+#[derive(Debug)]
+pub enum Event {
+    /// The delivery reports of one or more produced messages.
+    DeliveryReport(Vec<EventDeliveryResult>),
+    /// A message fetched by a consumer, or a consumption error.
+    Message(KafkaResult<OwnedMessage>),
+    /// A rebalance of the consumer group.
+    Rebalance(RebalanceEvent),
+    /// The result of an offset commit, with the committed offsets.
+    OffsetCommit(KafkaResult<()>, TopicPartitionList),
+    /// A global error, with a description of the error.
+    Error(KafkaError, String),
+    /// The statistics of the client. Statistics are emitted every `statistics.interval.ms`.
+    Stats(Statistics),
+    /// The raw JSON of statistics that couldn't be parsed into `Statistics`, as received by
+    /// `Context::stats_raw`. The parsing error is logged.
+    StatsRaw(String),
+    /// A log line, with its level and facility.
+    Log(RDKafkaLogLevel, String, String),
+}
+
+/// A client whose events are consumed by polling them with `poll_event`, instead of being
+/// delivered to the callbacks of the context. Refer to the module documentation for more
+/// information.
+pub struct EventConsumer<C: Context> {
+    // The queue must be destroyed before the client.
+    queue: NativeQueue,
+    client: Client<C>,
+}
+
+impl<C: Context> EventConsumer<C> {
+    /// Creates a new `EventConsumer`, using a client of the specified type. The context will only
+    /// receive the callbacks not covered by the events.
+    pub fn new(config: &ClientConfig, client_type: RDKafkaType, context: C) -> KafkaResult<EventConsumer<C>> {
+        let native_config = config.create_native_config()?;
+        unsafe { rdsys::rd_kafka_conf_set_events(native_config.ptr(), ENABLED_EVENTS) };
+        let client = Client::new(config, native_config, client_type, context)?;
+        if config.get("log.queue") == Some("true") {
+            // Forwards the log events to the main queue.
+            let ret = unsafe { rdsys::rd_kafka_set_log_queue(client.native_ptr(), ptr::null_mut()) };
+            if ret.is_error() {
+                return Err(KafkaError::ClientCreation(format!("Failed to set log queue: {:?}", ret)));
+            }
+        }
+        let queue = match client_type {
+            RDKafkaType::RD_KAFKA_CONSUMER => unsafe {
+                // Forwards the main queue to the consumer queue, which also receives the messages.
+                rdsys::rd_kafka_poll_set_consumer(client.native_ptr());
+                rdsys::rd_kafka_queue_get_consumer(client.native_ptr())
+            },
+            RDKafkaType::RD_KAFKA_PRODUCER => unsafe { rdsys::rd_kafka_queue_get_main(client.native_ptr()) },
+        };
+        Ok(EventConsumer { queue: NativeQueue::from_ptr(queue), client })
+    }
+
+    /// Returns a reference to the underlying client.
+    pub fn client(&self) -> &Client<C> {
+        &self.client
+    }
+
+    /// Returns the next event, or `None` if no event was received within `timeout_ms`
+    /// milliseconds. Set the timeout to 0 to make the call non-blocking, or to -1 to block until
+    /// an event is received.
+    pub fn poll_event(&self, timeout_ms: i32) -> Option<Event> {
+        loop {
+            let event_ptr = unsafe { rdsys::rd_kafka_queue_poll(self.queue.ptr(), timeout_ms) };
+            if event_ptr.is_null() {
+                return None;
+            }
+            let event = unsafe { event_from_ptr(event_ptr, self.client.opaque_context()) };
+            unsafe { rdsys::rd_kafka_event_destroy(event_ptr) };
+            // Events that can't be converted are skipped.
+            if event.is_some() {
+                return event;
+            }
+        }
+    }
+
+    /// Subscribes a consumer to the specified topics.
+    pub fn subscribe(&self, topics: &[&str]) -> KafkaResult<()> {
+        let mut tpl = TopicPartitionList::new();
+        for topic in topics {
+            tpl.add_topic_unassigned(topic);
+        }
+        let ret_code = unsafe { rdsys::rd_kafka_subscribe(self.client.native_ptr(), tpl.ptr()) };
+        if ret_code.is_error() {
+            let error = unsafe { cstr_to_owned(rdsys::rd_kafka_err2str(ret_code)) };
+            return Err(KafkaError::Subscription(error));
+        };
+        Ok(())
+    }
+
+    /// Updates the assignment of a consumer. This method should be called with the partitions of
+    /// every `RebalanceEvent::Assign` event, and with `None` for every `RebalanceEvent::Revoke`
+    /// or `RebalanceEvent::Error` event.
+    pub fn assign(&self, assignment: Option<&TopicPartitionList>) -> KafkaResult<()> {
+        let tpl_ptr = assignment.map(|tpl| tpl.ptr()).unwrap_or_else(ptr::null_mut);
+        let ret_code = unsafe { rdsys::rd_kafka_assign(self.client.native_ptr(), tpl_ptr) };
+        if ret_code.is_error() {
+            let error = unsafe { cstr_to_owned(rdsys::rd_kafka_err2str(ret_code)) };
+            return Err(KafkaError::Subscription(error));
+        };
+        Ok(())
+    }
+
+    /// Sends a copy of the payload and key provided to the specified topic, using a producer, in
+    /// the same way as `BaseProducer::send_copy`. The result of the delivery will be returned as
+    /// an `Event::DeliveryReport`.
+    pub fn send_copy<P, K>(
+        &self,
+        topic_name: &str,
+        partition: Option<i32>,
+        payload: Option<&P>,
+        key: Option<&K>,
+        timestamp: Option<i64>
+    ) -> KafkaResult<()>
+        where K: ToBytes + ?Sized,
+              P: ToBytes + ?Sized {
+        // The message keeps a reference to the topic handle, which can be dropped right away.
+        let native_topic = self.client.native_topic(topic_name)?;
+        let produce_error = unsafe {
+            produce_copy(self.client.native_ptr(), native_topic.ptr(), partition, payload, key, ptr::null_mut(), timestamp)
+        };
+        if produce_error.is_error() {
+            Err(KafkaError::MessageProduction(produce_error.into()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Converts a native event to an `Event`. Returns `None` for unknown events and for events whose
+/// content can't be read. Statistics are also used to notify the context of broker state changes.
+/// The native event is not destroyed.
+unsafe fn event_from_ptr<C: Context>(event: *mut RDKafkaEvent, opaque_context: &OpaqueContext<C>) -> Option<Event> {
+    match rdsys::rd_kafka_event_type(event) as u32 {
+        rdsys::RD_KAFKA_EVENT_DR => {
+            let mut results = Vec::with_capacity(rdsys::rd_kafka_event_message_count(event));
+            loop {
+                let message_ptr = rdsys::rd_kafka_event_message_next(event);
+                if message_ptr.is_null() {
+                    break;
+                }
+                results.push(detach_event_message(message_ptr, &event));
+            }
+            Some(Event::DeliveryReport(results))
+        },
+        rdsys::RD_KAFKA_EVENT_FETCH => {
+            let message_ptr = rdsys::rd_kafka_event_message_next(event);
+            if message_ptr.is_null() {
+                return None;
+            }
+            let result = detach_event_message(message_ptr, &event).map_err(|(e, _)| e);
+            Some(Event::Message(result))
+        },
+        rdsys::RD_KAFKA_EVENT_REBALANCE => {
+            let tpl = copy_event_partitions(event);
+            let rebalance = match rdsys::rd_kafka_event_error(event) {
+                RDKafkaRespErr::RD_KAFKA_RESP_ERR__ASSIGN_PARTITIONS => RebalanceEvent::Assign(tpl),
+                RDKafkaRespErr::RD_KAFKA_RESP_ERR__REVOKE_PARTITIONS => RebalanceEvent::Revoke(tpl),
+                e => RebalanceEvent::Error(KafkaError::Global(e.into())),
+            };
+            Some(Event::Rebalance(rebalance))
+        },
+        rdsys::RD_KAFKA_EVENT_OFFSET_COMMIT => {
+            let tpl = copy_event_partitions(event);
+            let err = rdsys::rd_kafka_event_error(event);
+            let result = if err.is_error() {
+                Err(KafkaError::ConsumerCommit(err.into()))
+            } else {
+                Ok(())
+            };
+            Some(Event::OffsetCommit(result, tpl))
+        },
+        rdsys::RD_KAFKA_EVENT_ERROR => {
+            let err = rdsys::rd_kafka_event_error(event);
+            let reason = cstr_to_owned(rdsys::rd_kafka_event_error_string(event));
+            Some(Event::Error(KafkaError::Global(err.into()), reason))
+        },
+        rdsys::RD_KAFKA_EVENT_STATS => {
+            let json = cstr_to_owned(rdsys::rd_kafka_event_stats(event));
+            match serde_json::from_str(&json) {
+                Ok(stats) => {
+                    opaque_context.notify_broker_state_changes(&stats);
+                    Some(Event::Stats(stats))
+                },
+                Err(e) => {
+                    error!("Could not parse statistics JSON: {}", e);
+                    Some(Event::StatsRaw(json))
+                }
+            }
+        },
+        rdsys::RD_KAFKA_EVENT_LOG => {
+            let mut fac = ptr::null();
+            let mut buf = ptr::null();
+            let mut level = 0;
+            if rdsys::rd_kafka_event_log(event, &mut fac, &mut buf, &mut level) != 0 {
+                return None;
+            }
+            let fac = kafka_cstr(fac).trim().to_owned();
+            let log_message = kafka_cstr(buf).trim().to_owned();
+            Some(Event::Log(RDKafkaLogLevel::from_int(level), fac, log_message))
+        },
+        _ => None,
+    }
+}
+
+/// Copies the partition list contained in an event. If the event has no partition list, an empty
+/// list is returned.
+unsafe fn copy_event_partitions(event: *mut RDKafkaEvent) -> TopicPartitionList {
+    let tpl_ptr = rdsys::rd_kafka_event_topic_partition_list(event);
+    if tpl_ptr.is_null() {
+        TopicPartitionList::new()
+    } else {
+        TopicPartitionList::from_ptr(rdsys::rd_kafka_topic_partition_list_copy(tpl_ptr))
+    }
+}
+
+/// Copies a message contained in an event. The native message is owned by the event, and it's not
+/// freed.
+unsafe fn detach_event_message(message_ptr: *const RDKafkaMessage, event: &*mut RDKafkaEvent) -> EventDeliveryResult {
+    let delivery_result = BorrowedMessage::from_dr_callback(message_ptr as *mut RDKafkaMessage, event);
+    let owned_result = match delivery_result {
+        Ok(ref message) => Ok(message.detach()),
+        Err((ref e, ref message)) => Err((e.clone(), message.detach())),
+    };
+    match delivery_result {
+        Ok(message) => mem::forget(message),
+        Err((_, message)) => mem::forget(message),
+    }
+    owned_result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client::EmptyContext;
+    use error::RDKafkaError;
+
+    use std::time::{Duration, Instant};
+
+    /// Polls the consumer until an event matching the predicate is received, or panics if none is
+    /// received within 10 seconds.
+    fn poll_until<C: Context, F: Fn(&Event) -> bool>(consumer: &EventConsumer<C>, predicate: F) -> Event {
+        let start_time = Instant::now();
+        while start_time.elapsed() < Duration::from_secs(10) {
+            match consumer.poll_event(100) {
+                Some(event) => if predicate(&event) { return event },
+                None => {},
+            }
+        }
+        panic!("Event not received");
+    }
+
+    #[test]
+    fn test_event_consumer_no_events() {
+        let config = ClientConfig::new();
+        let consumer = EventConsumer::new(&config, RDKafkaType::RD_KAFKA_PRODUCER, EmptyContext::new()).unwrap();
+        assert!(consumer.client().name().starts_with("rdkafka#producer-"));
+        assert!(consumer.poll_event(0).is_none());
+    }
+
+    #[test]
+    fn test_event_consumer_error() {
+        let mut config = ClientConfig::new();
+        config.set("bootstrap.servers", "localhost:1");
+        let consumer = EventConsumer::new(&config, RDKafkaType::RD_KAFKA_PRODUCER, EmptyContext::new()).unwrap();
+        match poll_until(&consumer, |event| match *event { Event::Error(..) => true, _ => false }) {
+            Event::Error(KafkaError::Global(error), _) =>
+                assert!(error == RDKafkaError::BrokerTransportFailure || error == RDKafkaError::AllBrokersDown),
+            event => panic!("Unexpected event: {:?}", event),
+        }
+    }
+
+    #[test]
+    fn test_event_consumer_stats() {
+        let mut config = ClientConfig::new();
+        config.set("statistics.interval.ms", "100");
+        let consumer = EventConsumer::new(&config, RDKafkaType::RD_KAFKA_PRODUCER, EmptyContext::new()).unwrap();
+        match poll_until(&consumer, |event| match *event { Event::Stats(_) => true, _ => false }) {
+            Event::Stats(stats) => {
+                assert_eq!(stats.name, consumer.client().name());
+                assert_eq!(stats.client_type, "producer");
+            },
+            event => panic!("Unexpected event: {:?}", event),
+        }
+    }
+}
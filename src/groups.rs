@@ -2,36 +2,29 @@
 use rdsys;
 use rdsys::types::*;
 
-use std::ffi::CStr;
+use std::borrow::Cow;
 use std::slice;
 use std::fmt;
 
+use util::kafka_cstr;
+
 /// Group member information container.
 pub struct GroupMemberInfo(RDKafkaGroupMemberInfo);
 
 impl GroupMemberInfo {
     /// Return the id of the member.
-    pub fn id(&self) -> &str {
-        unsafe {
-            CStr::from_ptr(self.0.member_id).to_str()
-                .expect("Member id is not a valid UTF-8 string")
-        }
+    pub fn id(&self) -> Cow<str> {
+        unsafe { kafka_cstr(self.0.member_id) }
     }
 
     /// Return the client id of the member.
-    pub fn client_id(&self) -> &str {
-        unsafe {
-            CStr::from_ptr(self.0.client_id).to_str()
-                .expect("Client id is not a valid UTF-8 string")
-        }
+    pub fn client_id(&self) -> Cow<str> {
+        unsafe { kafka_cstr(self.0.client_id) }
     }
 
     /// Return the client host of the member.
-    pub fn client_host(&self) -> &str {
-        unsafe {
-            CStr::from_ptr(self.0.client_host).to_str()
-                .expect("Member host is not a valid UTF-8 string")
-        }
+    pub fn client_host(&self) -> Cow<str> {
+        unsafe { kafka_cstr(self.0.client_host) }
     }
 
     /// Return the metadata of the member
@@ -62,11 +55,8 @@ pub struct GroupInfo(RDKafkaGroupInfo);
 
 impl GroupInfo {
     /// Return the name of the group.
-    pub fn name(&self) -> &str {
-        unsafe {
-            CStr::from_ptr(self.0.group).to_str()
-                .expect("Group name is not a valid UTF-8 string")
-        }
+    pub fn name(&self) -> Cow<str> {
+        unsafe { kafka_cstr(self.0.group) }
     }
 
     /// Returns the members of the group.
@@ -75,27 +65,18 @@ impl GroupInfo {
     }
 
     /// Returns the state of the group.
-    pub fn state(&self) -> &str {
-        unsafe {
-            CStr::from_ptr(self.0.state).to_str()
-                .expect("State is not a valid UTF-8 string")
-        }
+    pub fn state(&self) -> Cow<str> {
+        unsafe { kafka_cstr(self.0.state) }
     }
 
     /// Returns the protocol of the group.
-    pub fn protocol(&self) -> &str {
-        unsafe {
-            CStr::from_ptr(self.0.protocol).to_str()
-                .expect("Protocol name is not a valid UTF-8 string")
-        }
+    pub fn protocol(&self) -> Cow<str> {
+        unsafe { kafka_cstr(self.0.protocol) }
     }
 
     /// Returns the protocol type of the group.
-    pub fn protocol_type(&self) -> &str {
-        unsafe {
-            CStr::from_ptr(self.0.protocol_type).to_str()
-                .expect("Protocol type is not a valid UTF-8 string")
-        }
+    pub fn protocol_type(&self) -> Cow<str> {
+        unsafe { kafka_cstr(self.0.protocol_type) }
     }
 }
 
@@ -109,6 +90,9 @@ impl fmt::Debug for GroupInfo {
 /// the native resources when dropped.
 pub struct GroupList(*const RDKafkaGroupList);
 
+// The group list is not modified after creation, and can be freed from any thread.
+unsafe impl Send for GroupList {}
+
 impl GroupList {
     /// Creates a new group list given a pointer to the native rdkafka-sys group list structure.
     pub fn from_ptr(ptr: *const RDKafkaGroupList) -> GroupList {
@@ -128,6 +128,16 @@
 //! to check the version of the library installed in the system, and it will configure the
 //! compiler to use dynamic linking.
 //!
+//! The same happens if the `RDKAFKA_SYS_USE_PKG_CONFIG` environment variable is set, which
+//! can be used to link a version of librdkafka installed with Homebrew on macOS. On Windows,
+//! where the bundled sources can't be built, the library is always looked up in the system:
+//! first with `vcpkg`, then with `pkg-config`.
+//!
+//! When building the bundled sources, the build script fetches the librdkafka git submodule if
+//! the sources are missing. Vendored sources are used as they are, without invoking git; set
+//! the `RDKAFKA_SYS_SKIP_SUBMODULE` environment variable to make the build fail instead of
+//! running git, for instance in hermetic build environments.
+//!
 //! ## Compiling from sources
 //!
 //! To compile from sources, you'll have to update the submodule containing librdkafka:
@@ -199,6 +209,12 @@
 //! To enable debugging in your project, make sure you initialize the logger with
 //! `env_logger::init()` or equivalent.
 //!
+//! If the `tracing` feature is enabled, log lines coming from librdkafka will be emitted as
+//! [tracing] events instead, with the librdkafka facility stored in the `fac` field. The format
+//! of the log lines can be customized by overriding `Context::format_log`.
+//!
+//! [tracing]: https://github.com/tokio-rs/tracing
+//!
 
 //>alloc_system
 
@@ -206,6 +222,9 @@
 #[macro_use] extern crate serde_derive;
 extern crate serde_json;
 extern crate futures;
+extern crate libc;
+#[cfg(feature = "tracing")]
+extern crate tracing;
 
 extern crate rdkafka_sys as rdsys;
 
@@ -215,6 +234,7 @@ pub mod client;
 pub mod config;
 pub mod consumer;
 pub mod error;
+pub mod event;
 pub mod groups;
 pub mod message;
 pub mod metadata;
@@ -226,5 +246,5 @@ pub mod util;
 // Re-export
 pub use config::{ClientConfig, TopicConfig};
 pub use client::Context;
-pub use message::{Message, Timestamp};
+pub use message::{Message, Timestamp, TimestampType};
 pub use topic_partition_list::{Offset, TopicPartitionList};
@@ -2,30 +2,66 @@
 use rdsys;
 use rdsys::types::*;
 
-use std::ffi::CStr;
+use std::borrow::Cow;
 use std::fmt;
 use std::marker::PhantomData;
 use std::slice;
 use std::str;
 
 use error::{IsError, KafkaError, KafkaResult};
+use util::kafka_cstr;
 
 
 /// Timestamp of a message
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Timestamp {
+    /// The timestamp is not available.
     NotAvailable,
+    /// The timestamp was set by the producer, or by the client library when the message was
+    /// created.
     CreateTime(i64),
+    /// The timestamp was assigned by the broker when the message was appended to the log. This is
+    /// the case for topics configured with `message.timestamp.type=LogAppendTime`.
     LogAppendTime(i64)
 }
 
+/// The type of the timestamp of a message.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TimestampType {
+    /// The timestamp is not available.
+    NotAvailable,
+    /// The timestamp was set by the producer, or by the client library.
+    CreateTime,
+    /// The timestamp was assigned by the broker.
+    LogAppendTime,
+}
+
 impl Timestamp {
+    /// Returns the type of the timestamp.
+    pub fn timestamp_type(&self) -> TimestampType {
+        match *self {
+            Timestamp::NotAvailable => TimestampType::NotAvailable,
+            Timestamp::CreateTime(_) => TimestampType::CreateTime,
+            Timestamp::LogAppendTime(_) => TimestampType::LogAppendTime,
+        }
+    }
+
+    /// Returns the timestamp in milliseconds since the epoch, if available.
     pub fn to_millis(&self) -> Option<i64> {
         match *self {
             Timestamp::NotAvailable | Timestamp::CreateTime(-1) | Timestamp::LogAppendTime(-1) => None,
             Timestamp::CreateTime(t) | Timestamp::LogAppendTime(t) => Some(t),
         }
     }
+
+    /// Returns true if the timestamp was assigned by the broker. When returned by a delivered
+    /// message, this means that the timestamp provided by the producer was ignored.
+    pub fn is_log_append_time(&self) -> bool {
+        match *self {
+            Timestamp::LogAppendTime(_) => true,
+            _ => false,
+        }
+    }
 }
 
 /// The `Message` trait provides access to the fields of a generic Kafka message.
@@ -36,8 +72,9 @@ pub trait Message {
     /// Returns the payload of the message, or None if there is no payload.
     fn payload(&self) -> Option<&[u8]>;
 
-    /// Returns the source topic of the message.
-    fn topic(&self) -> &str;
+    /// Returns the source topic of the message. Invalid UTF-8 sequences are replaced with the
+    /// replacement character.
+    fn topic(&self) -> Cow<str>;
 
     /// Returns the partition number where the message is stored.
     fn partition(&self) -> i32;
@@ -45,9 +82,27 @@ pub trait Message {
     /// Returns the offset of the message.
     fn offset(&self) -> i64;
 
-    /// Returns the message timestamp for a consumed message if available.
+    /// Returns the partition and the offset of the message. In a delivery callback, this is
+    /// where a successfully delivered message has been stored.
+    fn partition_offset(&self) -> (i32, i64) {
+        (self.partition(), self.offset())
+    }
+
+    /// Returns the message timestamp for a consumed message if available. For a message received
+    /// in a delivery callback, the timestamp is the one assigned by the broker if the topic is
+    /// configured to use `LogAppendTime`, and the one provided by the producer otherwise.
     fn timestamp(&self) -> Timestamp;
 
+    /// Returns the type of the timestamp of the message.
+    fn timestamp_type(&self) -> TimestampType {
+        self.timestamp().timestamp_type()
+    }
+
+    /// Returns the timestamp of the message in milliseconds since the epoch, if available.
+    fn timestamp_millis(&self) -> Option<i64> {
+        self.timestamp().to_millis()
+    }
+
     /// Converts the raw bytes of the payload to a reference of the specified type, that points to the
     /// same data inside the message and without performing any memory allocation
     fn payload_view<P: ?Sized + FromBytes>(&self) -> Option<Result<&P, P::Error>> {
@@ -150,13 +205,35 @@ impl<'a> BorrowedMessage<'a> {
         unsafe { (*self.ptr).len }
     }
 
+    /// Returns a mutable reference to the payload of the message, or None if there is no payload.
+    /// This can be used to transform the payload in place, without copying it.
+    /// ## Safety
+    /// The payload resides in the receive buffer of the consumer, which is owned by librdkafka.
+    /// The caller must make sure that no other reference to the payload is alive while the
+    /// returned reference is used, and that the reference is not used after the message is
+    /// dropped.
+    pub unsafe fn payload_mut(&mut self) -> Option<&mut [u8]> {
+        if (*self.ptr).payload.is_null() {
+            None
+        } else {
+            Some(slice::from_raw_parts_mut::<u8>((*self.ptr).payload as *mut u8, (*self.ptr).len))
+        }
+    }
+
+    /// Returns the timestamp of a message received in a delivery callback, in milliseconds since
+    /// the epoch. If the topic is configured to use `LogAppendTime`, this is the timestamp
+    /// assigned by the broker; otherwise it's the one the message was produced with.
+    pub fn delivery_timestamp(&self) -> Option<i64> {
+        self.timestamp().to_millis()
+    }
+
     /// Clones the content of the `BorrowedMessage` and returns an `OwnedMessage`, that can
     /// outlive the consumer. This operation requires memory allocation and can be expensive.
     pub fn detach(&self) -> OwnedMessage {
         OwnedMessage {
             key: self.key().map(|k| k.to_vec()),
             payload: self.payload().map(|p| p.to_vec()),
-            topic: self.topic().to_owned(),
+            topic: self.topic().into_owned(),
             timestamp: self.timestamp(),
             partition: self.partition(),
             offset: self.offset(),
@@ -185,13 +262,9 @@ impl<'a> Message for BorrowedMessage<'a> {
         }
     }
 
-    fn topic(&self) -> &str {
-         unsafe {
-             CStr::from_ptr(rdsys::rd_kafka_topic_name((*self.ptr).rkt))
-                 .to_str()
-                 .expect("Topic name is not valid UTF-8")
-         }
-     }
+    fn topic(&self) -> Cow<str> {
+        unsafe { kafka_cstr(rdsys::rd_kafka_topic_name((*self.ptr).rkt)) }
+    }
 
     fn partition(&self) -> i32 {
         unsafe { (*self.ptr).partition }
@@ -235,7 +308,7 @@ impl<'a> Drop for BorrowedMessage<'a> {
 /// An `OwnedMessage` can be created from a `BorrowedMessage` using the `detach` method.
 /// `OwnedMessage`s don't hold any reference to the consumer, and don't use any memory inside the
 /// consumer buffer.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct OwnedMessage {
     payload: Option<Vec<u8>>,
     key: Option<Vec<u8>>,
@@ -274,8 +347,8 @@ impl Message for OwnedMessage {
         }
     }
 
-    fn topic(&self) -> &str {
-        self.topic.as_ref()
+    fn topic(&self) -> Cow<str> {
+        Cow::Borrowed(&self.topic)
     }
 
     fn partition(&self) -> i32 {
@@ -354,3 +427,23 @@ impl ToBytes for () {
         &[]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_timestamp_type() {
+        let message = OwnedMessage::new(None, None, "topic".to_owned(), Timestamp::LogAppendTime(1000), 0, 0);
+        assert_eq!(message.timestamp_type(), TimestampType::LogAppendTime);
+        assert_eq!(message.timestamp_millis(), Some(1000));
+
+        let message = OwnedMessage::new(None, None, "topic".to_owned(), Timestamp::CreateTime(-1), 0, 0);
+        assert_eq!(message.timestamp_type(), TimestampType::CreateTime);
+        assert_eq!(message.timestamp_millis(), None);
+
+        let message = OwnedMessage::new(None, None, "topic".to_owned(), Timestamp::NotAvailable, 0, 0);
+        assert_eq!(message.timestamp_type(), TimestampType::NotAvailable);
+        assert_eq!(message.timestamp_millis(), None);
+    }
+}
@@ -1,11 +1,12 @@
 //! Cluster metadata.
-use std::ffi::CStr;
+use std::borrow::Cow;
 use std::slice;
 
 use rdsys;
 use rdsys::types::*;
 
-use error::IsError;
+use error::{IsError, KafkaError, KafkaResult};
+use util::kafka_cstr;
 
 /// Broker metadata information.
 pub struct MetadataBroker(RDKafkaMetadataBroker);
@@ -17,12 +18,8 @@ impl MetadataBroker {
     }
 
     /// Returns the host name of the broker.
-    pub fn host(&self) -> &str {
-        unsafe {
-            CStr::from_ptr(self.0.host)
-                .to_str()
-                .expect("Broker host is not a valid UTF-8 string")
-        }
+    pub fn host(&self) -> Cow<str> {
+        unsafe { kafka_cstr(self.0.host) }
     }
 
     /// Returns the port of the broker.
@@ -71,15 +68,12 @@ pub struct MetadataTopic(RDKafkaMetadataTopic);
 
 impl MetadataTopic {
     /// Returns the name of the topic.
-    pub fn name(&self) -> &str {
-        unsafe {
-            CStr::from_ptr(self.0.topic)
-                .to_str()
-                .expect("Topic name is not a valid UTF-8 string")
-        }
+    pub fn name(&self) -> Cow<str> {
+        unsafe { kafka_cstr(self.0.topic) }
     }
 
-    /// Returns the partition metadata information for all the partitions.
+    /// Returns the partition metadata information for all the partitions. The slice points
+    /// directly to the native metadata: no copy is performed.
     pub fn partitions(&self) -> &[MetadataPartition] {
         unsafe { slice::from_raw_parts(self.0.partitions as *const MetadataPartition, self.0.partition_cnt as usize) }
     }
@@ -92,6 +86,15 @@ impl MetadataTopic {
             None
         }
     }
+
+    /// Returns `Err(KafkaError::MetadataFetch)` if the topic metadata contains an error, for
+    /// example if the topic doesn't exist, and `Ok` otherwise.
+    pub fn to_result(&self) -> KafkaResult<()> {
+        match self.error() {
+            Some(err) => Err(KafkaError::MetadataFetch(err.into())),
+            None => Ok(()),
+        }
+    }
 }
 
 /// Metadata container. This structure wraps the metadata pointer returned by rdkafka-sys,
@@ -104,18 +107,16 @@ impl Metadata {
         Metadata(ptr)
     }
 
-    /// Returns the id of the broker originating this metadata.
+    /// Returns the id of the broker originating this metadata. Useful to find out which broker
+    /// served stale metadata.
     pub fn orig_broker_id(&self) -> i32 {
         unsafe { (*self.0).orig_broker_id }
     }
 
-    /// Returns the hostname of the broker originating this metadata.
-    pub fn orig_broker_name(&self) -> &str {
-        unsafe {
-            CStr::from_ptr((*self.0).orig_broker_name)
-                .to_str()
-                .expect("Broker name is not a valid UTF-8 string")
-        }
+    /// Returns the name of the broker originating this metadata, as used by librdkafka in its
+    /// logs (e.g. `localhost:9092/0`).
+    pub fn orig_broker_name(&self) -> Cow<str> {
+        unsafe { kafka_cstr((*self.0).orig_broker_name) }
     }
 
     /// Returns the metadata information for all the brokers in the cluster.
@@ -123,7 +124,8 @@ impl Metadata {
         unsafe { slice::from_raw_parts((*self.0).brokers as *const MetadataBroker, (*self.0).broker_cnt as usize) }
     }
 
-    /// Returns the metadata information for all the topics in the cluster.
+    /// Returns the metadata information for all the topics in the cluster. The slice points
+    /// directly to the native metadata: no copy is performed.
     pub fn topics(&self) -> &[MetadataTopic] {
         unsafe { slice::from_raw_parts((*self.0).topics as *const MetadataTopic, (*self.0).topic_cnt as usize) }
     }
@@ -28,19 +28,25 @@ use rdsys::rd_kafka_vtype_t::*;
 use rdsys::types::*;
 use rdsys;
 
-use client::{Client, Context};
+use client::{Client, Context, NativeTopic, OpaqueContext};
 use config::{ClientConfig, FromClientConfig, FromClientConfigAndContext};
-use error::{KafkaError, KafkaResult, IsError};
-use message::{BorrowedMessage, ToBytes};
+use error::{KafkaError, KafkaResult, IsError, RDKafkaError};
+use message::{BorrowedMessage, Message, ToBytes};
+use util::duration_to_millis;
 
-use std::ffi::CString;
+use std::collections::HashMap;
 use std::os::raw::c_void;
 use std::mem;
 use std::ptr;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicIsize, Ordering};
+use std::time::{Duration, Instant};
 
 pub use message::DeliveryResult;
 
+/// Default value of the `message.max.bytes` configuration parameter in librdkafka.
+const DEFAULT_MESSAGE_MAX_BYTES: usize = 1_000_000;
+
 //
 // ********** PRODUCER CONTEXT **********
 //
@@ -73,21 +79,88 @@ impl ProducerContext for EmptyProducerContext {
 /// delivered.
 unsafe extern "C" fn delivery_cb<C: ProducerContext>(
         _client: *mut RDKafka, msg: *const RDKafkaMessage, _opaque: *mut c_void) {
-    let producer_context = Box::from_raw(_opaque as *mut C);
+    let producer_context = OpaqueContext::<C>::from_opaque(_opaque).context();
     let delivery_context = Box::from_raw((*msg)._private as *mut C::DeliveryContext);
     let owner = 42u8;
     // Wrap the message pointer into a BorrowedMessage that will only live for the body of this
     // function.
     let delivery_result = BorrowedMessage::from_dr_callback(msg as *mut RDKafkaMessage, &owner);
     trace!("Delivery event received: {:?}", delivery_result);
-    (*producer_context).delivery(&delivery_result, (*delivery_context));
-    mem::forget(producer_context); // Do not free the producer context
+    producer_context.delivery(&delivery_result, (*delivery_context));
     match delivery_result {        // Do not free the message, librdkafka will do it for us
         Ok(message) => mem::forget(message),
         Err((_, message)) => mem::forget(message),
     }
 }
 
+/// Enqueues a copy of the payload and key provided in the producer queue of the client, and
+/// returns the error code of `rd_kafka_producev`. The opaque pointer is returned in the delivery
+/// report of the message. This function is for internal use only: use `BaseProducer::send_copy`
+/// instead.
+pub unsafe fn produce_copy<P, K>(
+    native_ptr: *mut RDKafka,
+    topic_ptr: *mut RDKafkaTopic,
+    partition: Option<i32>,
+    payload: Option<&P>,
+    key: Option<&K>,
+    opaque_ptr: *mut c_void,
+    timestamp: Option<i64>
+) -> RDKafkaRespErr
+    where K: ToBytes + ?Sized,
+          P: ToBytes + ?Sized {
+    let (payload_ptr, payload_len) = match payload.map(P::to_bytes) {
+        None => (ptr::null_mut(), 0),
+        Some(p) => (p.as_ptr() as *mut c_void, p.len()),
+    };
+    let (key_ptr, key_len) = match key.map(K::to_bytes) {
+        None => (ptr::null_mut(), 0),
+        Some(k) => (k.as_ptr() as *mut c_void, k.len()),
+    };
+    rdsys::rd_kafka_producev(
+        native_ptr,
+        RD_KAFKA_VTYPE_RKT, topic_ptr,
+        RD_KAFKA_VTYPE_PARTITION, partition.unwrap_or(-1),
+        RD_KAFKA_VTYPE_MSGFLAGS, rdsys::RD_KAFKA_MSG_F_COPY as i32,
+        RD_KAFKA_VTYPE_VALUE, payload_ptr, payload_len,
+        RD_KAFKA_VTYPE_KEY, key_ptr, key_len,
+        RD_KAFKA_VTYPE_OPAQUE, opaque_ptr,
+        RD_KAFKA_VTYPE_TIMESTAMP, timestamp.unwrap_or(0),
+        RD_KAFKA_VTYPE_END
+    )
+}
+
+//
+// ********** BATCH RECORD **********
+//
+
+/// A message to be sent with `BaseProducer::produce_batch`. After the batch has been enqueued,
+/// the record contains the error that prevented the enqueueing of the message, if any.
+#[derive(Debug)]
+pub struct BatchRecord<'a> {
+    payload: Option<&'a [u8]>,
+    key: Option<&'a [u8]>,
+    error: Option<KafkaError>,
+}
+
+impl<'a> BatchRecord<'a> {
+    /// Creates a new record with the specified payload and key.
+    pub fn new<P, K>(payload: Option<&'a P>, key: Option<&'a K>) -> BatchRecord<'a>
+        where K: ToBytes + ?Sized,
+              P: ToBytes + ?Sized {
+        BatchRecord {
+            payload: payload.map(P::to_bytes),
+            key: key.map(K::to_bytes),
+            error: None,
+        }
+    }
+
+    /// Returns the error that prevented the message from being enqueued, or `None` if the message
+    /// was enqueued successfully, or if the batch hasn't been produced yet.
+    pub fn error(&self) -> Option<&KafkaError> {
+        self.error.as_ref()
+    }
+}
+
 //
 // ********** BASE PRODUCER **********
 //
@@ -105,7 +178,42 @@ impl<C: ProducerContext> FromClientConfigAndContext<C> for BaseProducer<C> {
         let native_config = config.create_native_config()?;
         unsafe { rdsys::rd_kafka_conf_set_dr_msg_cb(native_config.ptr(), Some(delivery_cb::<C>)) };
         let client = Client::new(config, native_config, RDKafkaType::RD_KAFKA_PRODUCER, context)?;
-        Ok(BaseProducer::from_client(client))
+        // Topic handles with a specific configuration are created in advance and kept alive, so
+        // that librdkafka will use them when producing to the corresponding topic.
+        let topics = config.topic_configs().iter()
+            .map(|(topic, topic_config)|
+                client.native_topic_with_config(topic, topic_config)
+                    .map(|native_topic| (topic.to_owned(), native_topic)))
+            .collect::<KafkaResult<HashMap<_, _>>>()?;
+        let message_max_bytes = config.get("message.max.bytes")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_MESSAGE_MAX_BYTES);
+        Ok(BaseProducer::from_client(client, topics, message_max_bytes))
+    }
+}
+
+/// The client of a producer, shared among all the clones of the same `BaseProducer`.
+struct ProducerClient<C: ProducerContext> {
+    // Topic handles, indexed by topic name. They must be destroyed before the client.
+    topics: RwLock<HashMap<String, NativeTopic>>,
+    client: Client<C>,
+    flush_on_drop_ms: AtomicIsize,
+    message_max_bytes: usize,
+}
+
+impl<C: ProducerContext> Drop for ProducerClient<C> {
+    /// If flush on drop is enabled, flushes the producer before the client is destroyed.
+    fn drop(&mut self) {
+        let timeout_ms = self.flush_on_drop_ms.load(Ordering::Relaxed);
+        if timeout_ms < 0 {
+            return;
+        }
+        trace!("Flushing producer before drop");
+        unsafe { rdsys::rd_kafka_flush(self.client.native_ptr(), timeout_ms as i32) };
+        let in_flight_count = unsafe { rdsys::rd_kafka_outq_len(self.client.native_ptr()) };
+        if in_flight_count > 0 {
+            warn!("Producer dropped with {} messages or requests still in queue", in_flight_count);
+        }
     }
 }
 
@@ -113,24 +221,53 @@ impl<C: ProducerContext> FromClientConfigAndContext<C> for BaseProducer<C> {
 /// serve queued delivery report callbacks. This producer can be cheaply cloned to
 /// create a new reference to the same underlying producer.
 pub struct BaseProducer<C: ProducerContext> {
-    client_arc: Arc<Client<C>>,
+    client_arc: Arc<ProducerClient<C>>,
 }
 
 impl<C: ProducerContext> BaseProducer<C> {
     /// Creates a base producer starting from a Client.
-    fn from_client(client: Client<C>) -> BaseProducer<C> {
-        BaseProducer { client_arc: Arc::new(client) }
+    fn from_client(client: Client<C>, topics: HashMap<String, NativeTopic>, message_max_bytes: usize) -> BaseProducer<C> {
+        let producer_client = ProducerClient {
+            topics: RwLock::new(topics),
+            client,
+            flush_on_drop_ms: AtomicIsize::new(-1),
+            message_max_bytes,
+        };
+        BaseProducer { client_arc: Arc::new(producer_client) }
     }
 
     /// Polls the producer. Regular calls to `poll` are required to process the events
-    /// and execute the message delivery callbacks.
+    /// and execute the message delivery callbacks, as well as the error and statistics callbacks.
+    /// It won't block for more than `timeout_ms` milliseconds. Returns the number of events served.
     pub fn poll(&self, timeout_ms: i32) -> i32 {
-        unsafe { rdsys::rd_kafka_poll(self.native_ptr(), timeout_ms) }
+        self.client().poll(timeout_ms)
+    }
+
+    /// Returns a reference to the underlying client.
+    pub fn client(&self) -> &Client<C> {
+        &self.client_arc.client
     }
 
     /// Returns a pointer to the native Kafka client.
     fn native_ptr(&self) -> *mut RDKafka {
-        self.client_arc.native_ptr()
+        self.client_arc.client.native_ptr()
+    }
+
+    /// Returns a pointer to the native handle of the specified topic, creating the handle if
+    /// needed. Handles are kept for the whole lifetime of the producer, so that repeated sends to
+    /// the same topic don't allocate a new handle every time.
+    fn native_topic_ptr(&self, topic_name: &str) -> KafkaResult<*mut RDKafkaTopic> {
+        if let Some(native_topic) = self.client_arc.topics.read().unwrap().get(topic_name) {
+            return Ok(native_topic.ptr());
+        }
+        let mut topics = self.client_arc.topics.write().unwrap();
+        if let Some(native_topic) = topics.get(topic_name) {
+            return Ok(native_topic.ptr());
+        }
+        let native_topic = self.client().native_topic(topic_name)?;
+        let native_topic_ptr = native_topic.ptr();
+        topics.insert(topic_name.to_owned(), native_topic);
+        Ok(native_topic_ptr)
     }
 
     /// Sends a copy of the payload and key provided to the specified topic. When no partition is
@@ -138,6 +275,10 @@ impl<C: ProducerContext> BaseProducer<C> {
     /// specified, a random partition will be used. Note that some errors will cause an error to be
     /// returned straight-away, such as partition not defined, while others will be returned in the
     /// delivery callback. To correctly handle errors, the delivery callback should be implemented.
+    /// If the size of the message exceeds `message.max.bytes`, a `KafkaError::MessageTooLarge`
+    /// error will be returned immediately. The timestamp is ignored by the broker if the topic is
+    /// configured to use `LogAppendTime`: in that case the timestamp assigned by the broker is
+    /// returned by the message in the delivery callback.
     pub fn send_copy<P, K>(
         &self,
         topic_name: &str,
@@ -149,43 +290,163 @@ impl<C: ProducerContext> BaseProducer<C> {
     ) -> KafkaResult<()>
         where K: ToBytes + ?Sized,
               P: ToBytes + ?Sized {
-        let (payload_ptr, payload_len) = match payload.map(P::to_bytes) {
-            None => (ptr::null_mut(), 0),
-            Some(p) => (p.as_ptr() as *mut c_void, p.len()),
-        };
-        let (key_ptr, key_len) = match key.map(K::to_bytes) {
-            None => (ptr::null_mut(), 0),
-            Some(k) => (k.as_ptr() as *mut c_void, k.len()),
-        };
+        let topic_ptr = self.native_topic_ptr(topic_name)?;
         let delivery_context_ptr = match delivery_context {
             Some(context) => Box::into_raw(context) as *mut c_void,
             None => ptr::null_mut(),
         };
-        let topic_name_c = CString::new(topic_name.to_owned())?;
         let produce_error = unsafe {
-            rdsys::rd_kafka_producev(
-                self.native_ptr(),
-                RD_KAFKA_VTYPE_TOPIC, topic_name_c.as_ptr(),
-                RD_KAFKA_VTYPE_PARTITION, partition.unwrap_or(-1),
-                RD_KAFKA_VTYPE_MSGFLAGS, rdsys::RD_KAFKA_MSG_F_COPY as i32,
-                RD_KAFKA_VTYPE_VALUE, payload_ptr, payload_len,
-                RD_KAFKA_VTYPE_KEY, key_ptr, key_len,
-                RD_KAFKA_VTYPE_OPAQUE, delivery_context_ptr,
-                RD_KAFKA_VTYPE_TIMESTAMP, timestamp.unwrap_or(0),
-                RD_KAFKA_VTYPE_END
-            )
+            produce_copy(self.native_ptr(), topic_ptr, partition, payload, key, delivery_context_ptr, timestamp)
+        };
+        if produce_error.is_error() && !delivery_context_ptr.is_null() {
+            // The message was not enqueued: the delivery callback won't be called, so the
+            // delivery context has to be freed here.
+            unsafe { drop(Box::from_raw(delivery_context_ptr as *mut C::DeliveryContext)) };
+        }
+        match produce_error {
+            RDKafkaRespErr::RD_KAFKA_RESP_ERR_MSG_SIZE_TOO_LARGE =>
+                Err(KafkaError::MessageTooLarge(self.client_arc.message_max_bytes)),
+            e if e.is_error() => Err(KafkaError::MessageProduction(e.into())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Sends the key and the payload of an existing message, such as a message received from a
+    /// consumer, to the specified topic. The data is copied directly from the buffer of the
+    /// message into the producer queue, without intermediate allocations. The original timestamp
+    /// is preserved, while the partition is selected by the underlying Kafka library.
+    pub fn send_borrowed<M: Message>(
+        &self,
+        message: &M,
+        topic_name: &str,
+        delivery_context: Option<Box<C::DeliveryContext>>
+    ) -> KafkaResult<()> {
+        self.send_copy(topic_name, None, message.payload(), message.key(), delivery_context,
+                       message.timestamp().to_millis())
+    }
+
+    /// Sends a copy of all the provided messages. Each message is sent to its own topic, and
+    /// topic, partition, key, payload and timestamp are taken from the message; if the partition is
+    /// negative, the partition will be selected by the underlying Kafka library. The delivery
+    /// context of each message is returned by `delivery_context`, called with the index of the
+    /// message. Returns the result of the enqueueing of every message: messages that couldn't be
+    /// enqueued won't be reported in the delivery callback.
+    pub fn send_to_topics<M, F>(&self, messages: &[M], mut delivery_context: F) -> Vec<KafkaResult<()>>
+        where M: Message,
+              F: FnMut(usize) -> Option<Box<C::DeliveryContext>> {
+        messages.iter()
+            .enumerate()
+            .map(|(index, message)| {
+                let partition = if message.partition() >= 0 { Some(message.partition()) } else { None };
+                self.send_copy(&message.topic(), partition, message.payload(), message.key(),
+                               delivery_context(index), message.timestamp().to_millis())
+            })
+            .collect()
+    }
+
+    /// Enqueues a batch of messages to the specified topic with a single call to librdkafka
+    /// (`rd_kafka_produce_batch`), reducing the per-message overhead of `send_copy` when
+    /// producing many small messages. This is a lower level method than `send_copy`: messages
+    /// don't have a timestamp, and they are all sent to the same partition, or to a partition
+    /// selected by the underlying Kafka library if no partition is specified. The payloads and
+    /// keys are copied (`RD_KAFKA_MSG_F_COPY`), so the records can be dropped as soon as the method
+    /// returns.
+    ///
+    /// The delivery context of each message is returned by `delivery_context`, called with the
+    /// index of the record. Returns the number of messages enqueued: the records of the messages
+    /// that couldn't be enqueued contain the corresponding error, and they won't be reported in
+    /// the delivery callback.
+    pub fn produce_batch<F>(
+        &self,
+        topic_name: &str,
+        partition: Option<i32>,
+        records: &mut [BatchRecord],
+        mut delivery_context: F
+    ) -> KafkaResult<usize>
+        where F: FnMut(usize) -> Option<Box<C::DeliveryContext>> {
+        let topic_ptr = self.native_topic_ptr(topic_name)?;
+        let mut native_messages = records.iter()
+            .enumerate()
+            .map(|(index, record)| {
+                let mut message: RDKafkaMessage = unsafe { mem::zeroed() };
+                if let Some(payload) = record.payload {
+                    message.payload = payload.as_ptr() as *mut c_void;
+                    message.len = payload.len();
+                }
+                if let Some(key) = record.key {
+                    message.key = key.as_ptr() as *mut c_void;
+                    message.key_len = key.len();
+                }
+                message._private = match delivery_context(index) {
+                    Some(context) => Box::into_raw(context) as *mut c_void,
+                    None => ptr::null_mut(),
+                };
+                message
+            })
+            .collect::<Vec<_>>();
+        let enqueued = unsafe {
+            rdsys::rd_kafka_produce_batch(topic_ptr, partition.unwrap_or(-1),
+                                          rdsys::RD_KAFKA_MSG_F_COPY as i32,
+                                          native_messages.as_mut_ptr(), native_messages.len() as i32)
         };
-        if produce_error.is_error() {
-            Err(KafkaError::MessageProduction(produce_error.into()))
-        } else {
-            Ok(())
+        for (record, message) in records.iter_mut().zip(native_messages.iter()) {
+            record.error = match message.err {
+                RDKafkaRespErr::RD_KAFKA_RESP_ERR_MSG_SIZE_TOO_LARGE =>
+                    Some(KafkaError::MessageTooLarge(self.client_arc.message_max_bytes)),
+                e if e.is_error() => Some(KafkaError::MessageProduction(e.into())),
+                _ => None,
+            };
+            if record.error.is_some() && !message._private.is_null() {
+                // The delivery callback won't be called for this message.
+                unsafe { drop(Box::from_raw(message._private as *mut C::DeliveryContext)) };
+            }
         }
+        Ok(enqueued.max(0) as usize)
     }
 
     /// Flushes the producer. Should be called before termination.
     pub fn flush(&self, timeout_ms: i32) {
         unsafe { rdsys::rd_kafka_flush(self.native_ptr(), timeout_ms) };
     }
+
+    /// Flushes the producer, calling `progress` every `interval_ms` milliseconds with the number
+    /// of messages and requests still waiting to be delivered. Useful to report the progress of
+    /// long flushes. Intervals shorter than one millisecond are rounded up to one millisecond.
+    /// Returns once all messages are delivered, or a `KafkaError::Flush` error containing
+    /// `RDKafkaError::OperationTimedOut` if some messages are still waiting after `timeout_ms`
+    /// milliseconds.
+    pub fn flush_with_progress<F: FnMut(i32)>(&self, timeout_ms: i32, interval_ms: i32, mut progress: F) -> KafkaResult<()> {
+        let interval_ms = interval_ms.max(1);
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms.max(0) as u64);
+        loop {
+            if self.in_flight_count() == 0 {
+                return Ok(());
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(KafkaError::Flush(RDKafkaError::OperationTimedOut));
+            }
+            let remaining_ms = duration_to_millis(deadline - now) as i32;
+            self.flush(interval_ms.min(remaining_ms.max(1)));
+            progress(self.in_flight_count());
+        }
+    }
+
+    /// Returns the number of messages and requests waiting to be sent to, or acknowledged by,
+    /// the broker.
+    pub fn in_flight_count(&self) -> i32 {
+        unsafe { rdsys::rd_kafka_outq_len(self.native_ptr()) }
+    }
+
+    /// Enables or disables the automatic flush of the producer when the last reference to it is
+    /// dropped. If a timeout is specified, the producer will wait up to `timeout_ms` milliseconds
+    /// for the queued messages to be delivered, and a warning will be logged if some messages are
+    /// still in queue after the timeout. The setting is shared among all the clones of the
+    /// producer. Flush on drop is disabled by default.
+    pub fn set_flush_on_drop(&self, timeout_ms: Option<i32>) {
+        let timeout_ms = timeout_ms.map(|t| t.max(0) as isize).unwrap_or(-1);
+        self.client_arc.flush_on_drop_ms.store(timeout_ms, Ordering::Relaxed);
+    }
 }
 
 impl<C: ProducerContext> Clone for BaseProducer<C> {
@@ -199,7 +460,8 @@ mod tests {
     // Just test that there are no panics, and that each struct implements the expected
     // traits (Clone, Send, Sync etc.). Behavior is tested in the integrations tests.
     use super::*;
-    use config::ClientConfig;
+    use config::{ClientConfig, TopicConfig};
+    use message::{OwnedMessage, Timestamp};
 
     // Verify that the producer is clone, according to documentation.
     #[test]
@@ -207,4 +469,91 @@ mod tests {
         let producer = ClientConfig::new().create::<BaseProducer<_>>().unwrap();
         let _producer_clone = producer.clone();
     }
+
+    // Verify that an empty producer can be dropped when flush on drop is enabled.
+    #[test]
+    fn test_base_producer_flush_on_drop() {
+        let producer = ClientConfig::new().create::<BaseProducer<_>>().unwrap();
+        producer.set_flush_on_drop(Some(100));
+        let producer_clone = producer.clone();
+        drop(producer);
+        drop(producer_clone);
+    }
+
+    #[test]
+    fn test_base_producer_message_too_large() {
+        let producer = ClientConfig::new()
+            .set("message.max.bytes", "1000")
+            .create::<BaseProducer<_>>()
+            .unwrap();
+        let payload = vec![0u8; 2000];
+        let result = producer.send_copy::<_, ()>("topic", None, Some(&payload), None, None, None);
+        assert_eq!(result, Err(KafkaError::MessageTooLarge(1000)));
+    }
+
+    #[test]
+    fn test_base_producer_topic_config() {
+        let producer = ClientConfig::new()
+            .set_topic_config("topic1", TopicConfig::new().set("request.required.acks", "all").finalize())
+            .set_topic_config("topic2", TopicConfig::new().set("request.required.acks", "0").finalize())
+            .create::<BaseProducer<_>>()
+            .unwrap();
+        let _producer_clone = producer.clone();
+    }
+
+    #[test]
+    fn test_base_producer_send_to_topics() {
+        let producer = ClientConfig::new().create::<BaseProducer<_>>().unwrap();
+        let messages = vec![
+            OwnedMessage::new(Some(vec![1]), None, "topic1".to_owned(), Timestamp::NotAvailable, -1, -1),
+            OwnedMessage::new(Some(vec![2]), None, "topic2".to_owned(), Timestamp::NotAvailable, -1, -1),
+            OwnedMessage::new(Some(vec![3]), None, "topic1".to_owned(), Timestamp::NotAvailable, -1, -1),
+        ];
+        let results = producer.send_to_topics(&messages, |_| None);
+        assert_eq!(results, vec![Ok(()), Ok(()), Ok(())]);
+        assert_eq!(producer.client_arc.topics.read().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_base_producer_produce_batch() {
+        let producer = ClientConfig::new()
+            .set("message.max.bytes", "1000")
+            .create::<BaseProducer<_>>()
+            .unwrap();
+        let large_payload = vec![0u8; 2000];
+        let mut records = vec![
+            BatchRecord::new(Some("payload1"), Some("key1")),
+            BatchRecord::new::<_, ()>(Some(&large_payload), None),
+            BatchRecord::new::<(), _>(None, Some("key3")),
+        ];
+        let enqueued = producer.produce_batch("topic", Some(0), &mut records, |_| None).unwrap();
+        assert_eq!(enqueued, 2);
+        assert_eq!(records[0].error(), None);
+        assert_eq!(records[1].error(), Some(&KafkaError::MessageTooLarge(1000)));
+        assert_eq!(records[2].error(), None);
+    }
+
+    // Messages can't be delivered without a broker: the flush should time out.
+    #[test]
+    fn test_base_producer_flush_with_progress_timeout() {
+        let producer = ClientConfig::new().create::<BaseProducer<_>>().unwrap();
+        assert_eq!(producer.flush_with_progress(100, 10, |_| ()), Ok(()));
+
+        producer.send_copy::<_, ()>("topic", None, Some("payload"), None, None, None).unwrap();
+        let mut progress_count = 0;
+        let result = producer.flush_with_progress(100, 0, |in_flight_count| {
+            assert!(in_flight_count > 0);
+            progress_count += 1;
+        });
+        assert_eq!(result, Err(KafkaError::Flush(RDKafkaError::OperationTimedOut)));
+        assert!(progress_count > 0);
+    }
+
+    #[test]
+    fn test_base_producer_send_borrowed() {
+        let producer = ClientConfig::new().create::<BaseProducer<_>>().unwrap();
+        let message = OwnedMessage::new(Some(vec![1, 2, 3]), Some(vec![4]), "source".to_owned(),
+                                        Timestamp::CreateTime(1000), 0, 10);
+        assert_eq!(producer.send_borrowed(&message, "destination", None), Ok(()));
+    }
 }
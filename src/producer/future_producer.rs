@@ -1,14 +1,15 @@
 use client::{Context, EmptyContext};
 use config::{ClientConfig, FromClientConfig, FromClientConfigAndContext, RDKafkaLogLevel};
-use producer::{BaseProducer, DeliveryResult, EmptyProducerContext, ProducerContext};
-use statistics::Statistics;
-use error::{KafkaError, KafkaResult};
+use producer::{AsyncProducer, BaseProducer, DeliveryResult, EmptyProducerContext, ProducerContext};
+use statistics::{BrokerState, Statistics};
+use error::{KafkaError, KafkaResult, RDKafkaError};
 use message::{Message, OwnedMessage, Timestamp, ToBytes};
 
 use futures::{self, Canceled, Complete, Future, Poll, Oneshot, Async};
+use log::LogLevel;
 
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread::{self, JoinHandle};
 
 //
@@ -24,6 +25,8 @@ use std::thread::{self, JoinHandle};
 pub struct PollingProducer<C: ProducerContext + 'static> {
     producer: BaseProducer<C>,
     should_stop: Arc<AtomicBool>,
+    // Number of messages rejected because the producer queue was full.
+    queue_full_count: AtomicUsize,
     handle: RwLock<Option<JoinHandle<()>>>,
 }
 
@@ -38,6 +41,7 @@ impl<C: ProducerContext + 'static> FromClientConfigAndContext<C> for PollingProd
         let polling_producer = PollingProducer {
             producer: BaseProducer::from_config_and_context(config, context)?,
             should_stop: Arc::new(AtomicBool::new(false)),
+            queue_full_count: AtomicUsize::new(0),
             handle: RwLock::new(None),
         };
         polling_producer.start();
@@ -51,7 +55,7 @@ impl<C: ProducerContext + 'static> PollingProducer<C> {
         let producer_clone = self.producer.clone();
         let should_stop = self.should_stop.clone();
         let handle = thread::Builder::new()
-            .name("polling thread".to_string())
+            .name(format!("rdkafka-poll-{}", self.producer.client().name()))
             .spawn(move || {
                 trace!("Polling thread loop started");
                 loop {
@@ -99,13 +103,19 @@ impl<C: ProducerContext + 'static> PollingProducer<C> {
     ) -> KafkaResult<()>
         where K: ToBytes + ?Sized,
               P: ToBytes + ?Sized {
-        self.producer.send_copy(topic, partition, payload, key, delivery_context, timestamp)
+        let result = self.producer.send_copy(topic, partition, payload, key, delivery_context, timestamp);
+        if result == Err(KafkaError::MessageProduction(RDKafkaError::QueueFull)) {
+            self.queue_full_count.fetch_add(1, Ordering::Relaxed);
+        }
+        result
     }
 }
 
 impl<C: ProducerContext + 'static> Drop for PollingProducer<C> {
     fn drop(&mut self) {
         trace!("Destroy PollingProducer");
+        // The polling thread holds a reference to the producer: it must be joined before the
+        // producer is dropped, so that the native client is destroyed by this thread.
         self.stop();
     }
 }
@@ -135,24 +145,109 @@ impl<C: Context + 'static> Context for FutureProducerContext<C> {
         self.wrapped_context.log(level, fac, log_message);
     }
 
+    fn format_log(&self, fac: &str, log_message: &str) -> String {
+        self.wrapped_context.format_log(fac, log_message)
+    }
+
     fn stats(&self, statistics: Statistics) {
         self.wrapped_context.stats(statistics);
     }
 
+    fn stats_raw(&self, json: &str) {
+        self.wrapped_context.stats_raw(json);
+    }
+
     fn error(&self, error: KafkaError, reason: &str) {
         self.wrapped_context.error(error, reason);
     }
+
+    fn error_log_level(&self, error: &KafkaError) -> Option<LogLevel> {
+        self.wrapped_context.error_log_level(error)
+    }
+
+    fn broker_state_change(&self, broker_name: &str, broker_id: i32, old_state: BrokerState, new_state: BrokerState) {
+        self.wrapped_context.broker_state_change(broker_name, broker_id, old_state, new_state);
+    }
+
+    fn throttle(&self, broker_name: &str, broker_id: i32, throttle_time_ms: i32) {
+        self.wrapped_context.throttle(broker_name, broker_id, throttle_time_ms);
+    }
+}
+
+/// The state of a batch of messages sent with `send_all`, shared among the delivery contexts
+/// of all the messages of the batch.
+struct BatchState {
+    results: Vec<Option<OwnedDeliveryResult>>,
+    remaining: usize,
+    tx: Option<Complete<Vec<OwnedDeliveryResult>>>,
+}
+
+/// Collects the delivery results of a batch of messages, and completes the corresponding future
+/// once all the results are available.
+struct BatchCollector {
+    state: Mutex<BatchState>,
+}
+
+impl BatchCollector {
+    /// Creates a new collector for a batch of `size` messages.
+    fn new(size: usize, tx: Complete<Vec<OwnedDeliveryResult>>) -> BatchCollector {
+        let state = BatchState {
+            results: (0..size).map(|_| None).collect(),
+            remaining: size,
+            tx: Some(tx),
+        };
+        let collector = BatchCollector { state: Mutex::new(state) };
+        if size == 0 {
+            collector.complete_batch(&mut collector.state.lock().expect("poison error"));
+        }
+        collector
+    }
+
+    /// Stores the delivery result of the message in position `index` of the batch.
+    fn complete(&self, index: usize, result: OwnedDeliveryResult) {
+        let mut state = self.state.lock().expect("poison error");
+        if state.results[index].is_none() {
+            state.results[index] = Some(result);
+            state.remaining -= 1;
+        }
+        if state.remaining == 0 {
+            self.complete_batch(&mut state);
+        }
+    }
+
+    /// Completes the future of the batch with all the delivery results.
+    fn complete_batch(&self, state: &mut BatchState) {
+        if let Some(tx) = state.tx.take() {
+            let results = state.results.drain(..)
+                .map(|result| result.expect("Missing delivery result"))
+                .collect();
+            let _ = tx.send(results);
+        }
+    }
+}
+
+/// The delivery context used by the `FutureProducer`: either the sender of a single message
+/// future, or the collector of a batch of messages together with the position of the message
+/// in the batch.
+enum FutureDeliveryContext {
+    Single(Complete<OwnedDeliveryResult>),
+    Batch(Arc<BatchCollector>, usize),
 }
 
 impl<C: Context + 'static> ProducerContext for FutureProducerContext<C> {
-    type DeliveryContext = Complete<OwnedDeliveryResult>;
+    type DeliveryContext = FutureDeliveryContext;
 
-    fn delivery(&self, delivery_result: &DeliveryResult, tx: Complete<OwnedDeliveryResult>) {
+    fn delivery(&self, delivery_result: &DeliveryResult, delivery_context: FutureDeliveryContext) {
         let owned_delivery_result = match delivery_result {
-            &Ok(ref message) => Ok((message.partition(), message.offset())),
+            &Ok(ref message) => Ok(message.partition_offset()),
             &Err((ref error, ref message)) => Err((error.clone(), message.detach())),
         };
-        let _ = tx.send(owned_delivery_result);   // TODO: handle error
+        match delivery_context {
+            FutureDeliveryContext::Single(tx) => {
+                let _ = tx.send(owned_delivery_result);   // TODO: handle error
+            },
+            FutureDeliveryContext::Batch(collector, index) => collector.complete(index, owned_delivery_result),
+        }
     }
 }
 
@@ -200,6 +295,14 @@ pub struct DeliveryFuture {
 
 // TODO: remove?
 impl DeliveryFuture {
+    /// Creates a `DeliveryFuture` that is already completed with the provided result. Mainly
+    /// useful for writing tests.
+    pub fn completed(result: OwnedDeliveryResult) -> DeliveryFuture {
+        let (tx, rx) = futures::oneshot();
+        let _ = tx.send(result);
+        DeliveryFuture { rx }
+    }
+
     pub fn close(&mut self) {
         self.rx.close();
     }
@@ -218,10 +321,40 @@ impl Future for DeliveryFuture {
     }
 }
 
+/// A `Future` wrapping the result of the production of a batch of messages.
+///
+/// Once completed, the future will contain the `OwnedDeliveryResult` of every message of the
+/// batch, in the same order the messages were provided.
+pub struct BatchDeliveryFuture {
+    rx: Oneshot<Vec<OwnedDeliveryResult>>,
+}
+
+impl Future for BatchDeliveryFuture {
+    type Item = Vec<OwnedDeliveryResult>;
+    type Error = Canceled;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        self.rx.poll()
+    }
+}
+
+impl BatchDeliveryFuture {
+    /// Creates a `BatchDeliveryFuture` that is already completed with the provided results.
+    /// Mainly useful for writing tests.
+    pub fn completed(results: Vec<OwnedDeliveryResult>) -> BatchDeliveryFuture {
+        let (tx, rx) = futures::oneshot();
+        let _ = tx.send(results);
+        BatchDeliveryFuture { rx }
+    }
+}
+
 impl<C: Context + 'static> FutureProducer<C> {
     /// Sends a copy of the payload and key provided to the specified topic. When no partition is
-    /// specified the underlying Kafka library picks a partition based on the key.
-    /// Returns a `DeliveryFuture`.
+    /// specified the underlying Kafka library picks a partition based on the key. The timestamp is
+    /// ignored by the broker if the topic is configured to use `LogAppendTime`.
+    /// Returns a `DeliveryFuture`. The future is completed once the message has been acknowledged:
+    /// the latency includes the time the message waits in the producer queue, up to the linger
+    /// time set with `ClientConfig::set_linger`.
     pub fn send_copy<P, K>(
         &self,
         topic: &str,
@@ -235,7 +368,8 @@ impl<C: Context + 'static> FutureProducer<C> {
         let (tx, rx) = futures::oneshot();
 
         // TODO: catch and retry on QueueFull
-        match self.inner.send_copy(topic, partition, payload, key, timestamp, Some(Box::new(tx))) {
+        let delivery_context = FutureDeliveryContext::Single(tx);
+        match self.inner.send_copy(topic, partition, payload, key, timestamp, Some(Box::new(delivery_context))) {
             Ok(_) => DeliveryFuture{ rx },
             Err(e) => {
                 let (tx, rx) = futures::oneshot();
@@ -253,13 +387,108 @@ impl<C: Context + 'static> FutureProducer<C> {
         }
     }
 
+    /// Sends a copy of all the provided messages, and returns a single future that will be
+    /// completed once the delivery of every message succeeded or failed. Topic, partition, key,
+    /// payload and timestamp are taken from each message; if the partition is negative, the
+    /// partition will be selected by the underlying Kafka library. This method is more efficient
+    /// than calling `send_copy` for every message and joining the resulting futures.
+    pub fn send_all<M: Message>(&self, messages: &[M]) -> BatchDeliveryFuture {
+        let (tx, rx) = futures::oneshot();
+        let collector = Arc::new(BatchCollector::new(messages.len(), tx));
+        for (index, message) in messages.iter().enumerate() {
+            let partition = if message.partition() >= 0 { Some(message.partition()) } else { None };
+            let timestamp = message.timestamp().to_millis();
+            let delivery_context = FutureDeliveryContext::Batch(collector.clone(), index);
+            let send_result = self.inner.send_copy(
+                &message.topic(), partition, message.payload(), message.key(), timestamp,
+                Some(Box::new(delivery_context)));
+            if let Err(e) = send_result {
+                let owned_message = OwnedMessage::new(
+                    message.payload().map(|p| p.to_vec()),
+                    message.key().map(|k| k.to_vec()),
+                    message.topic().into_owned(),
+                    message.timestamp(),
+                    message.partition(),
+                    message.offset()
+                );
+                collector.complete(index, Err((e, owned_message)));
+            }
+        }
+        BatchDeliveryFuture { rx }
+    }
+
     /// Stops the internal polling thread. The thread can also be stopped by moving
     /// the `FutureProducer` out of scope.
     pub fn stop(&self) {
         self.inner.stop();
     }
 
-    // TODO: add poll and flush
+    /// Flushes the producer, waiting until all the queued messages are delivered or the timeout
+    /// expires. Should be called before termination. Note that flushing doesn't make the producer
+    /// send the queued messages before the linger time expires: latency-critical messages should
+    /// be sent with a producer configured with a lower linger.
+    pub fn flush(&self, timeout_ms: i32) {
+        self.inner.producer.flush(timeout_ms);
+    }
+
+    /// Flushes the producer, reporting the progress periodically. See
+    /// `BaseProducer::flush_with_progress`.
+    pub fn flush_with_progress<F: FnMut(i32)>(&self, timeout_ms: i32, interval_ms: i32, progress: F) -> KafkaResult<()> {
+        self.inner.producer.flush_with_progress(timeout_ms, interval_ms, progress)
+    }
+
+    /// Returns the number of messages and requests waiting to be sent to, or acknowledged by,
+    /// the broker.
+    pub fn in_flight_count(&self) -> i32 {
+        self.inner.producer.in_flight_count()
+    }
+
+    /// Returns the number of messages that couldn't be sent because the producer queue was full,
+    /// since the creation of the producer. The counter is shared among all the clones of the
+    /// producer, and can be used to tune `queue.buffering.max.messages` and
+    /// `queue.buffering.max.kbytes`.
+    pub fn queue_full_count(&self) -> usize {
+        self.inner.queue_full_count.load(Ordering::Relaxed)
+    }
+
+    /// Enables or disables the automatic flush of the producer when the last reference to it is
+    /// dropped. See `BaseProducer::set_flush_on_drop`.
+    pub fn set_flush_on_drop(&self, timeout_ms: Option<i32>) {
+        self.inner.producer.set_flush_on_drop(timeout_ms);
+    }
+
+    // TODO: add poll
+}
+
+impl<C: Context + 'static> AsyncProducer for FutureProducer<C> {
+    fn send_copy<P, K>(
+        &self,
+        topic: &str,
+        partition: Option<i32>,
+        payload: Option<&P>,
+        key: Option<&K>,
+        timestamp: Option<i64>
+    ) -> DeliveryFuture
+        where K: ToBytes + ?Sized,
+              P: ToBytes + ?Sized {
+        FutureProducer::send_copy(self, topic, partition, payload, key, timestamp)
+    }
+
+    fn send_all<M: Message>(&self, messages: &[M]) -> BatchDeliveryFuture {
+        FutureProducer::send_all(self, messages)
+    }
+
+    fn flush(&self, timeout_ms: i32) {
+        FutureProducer::flush(self, timeout_ms)
+    }
+
+    fn flush_with_progress<F: FnMut(i32)>(&self, timeout_ms: i32, interval_ms: i32, progress: F) -> KafkaResult<()> {
+        FutureProducer::flush_with_progress(self, timeout_ms, interval_ms, progress)
+    }
+
+    fn in_flight_count(&self) -> i32 {
+        FutureProducer::in_flight_count(self)
+    }
 }
 
 #[cfg(test)]
@@ -295,4 +524,38 @@ mod tests {
             .create_with_context::<TestContext, FutureProducer<_>>(test_context).unwrap();
         let _producer_clone = producer.clone();
     }
+
+    // The polling thread should be named after the client, to be recognizable in thread dumps.
+    #[test]
+    fn test_future_producer_thread_name() {
+        let producer = ClientConfig::new().create::<FutureProducer<_>>().unwrap();
+        let handle = producer.inner.handle.read().unwrap();
+        let thread_name = handle.as_ref().unwrap().thread().name().unwrap().to_owned();
+        assert!(thread_name.starts_with("rdkafka-poll-rdkafka#producer-"));
+    }
+
+    // Messages rejected because the queue is full should be counted.
+    #[test]
+    fn test_future_producer_queue_full_count() {
+        let producer = ClientConfig::new()
+            .set("queue.buffering.max.messages", "1")
+            .create::<FutureProducer<_>>()
+            .unwrap();
+        let _first = producer.send_copy::<str, str>("topic", None, Some("payload"), None, None);
+        let second = producer.send_copy::<str, str>("topic", None, Some("payload"), None, None);
+        match second.wait() {
+            Ok(Err((KafkaError::MessageProduction(RDKafkaError::QueueFull), _))) => (),
+            e => panic!("Expected a QueueFull error: {:?}", e),
+        };
+        assert_eq!(producer.queue_full_count(), 1);
+        assert_eq!(producer.clone().queue_full_count(), 1);
+    }
+
+    // An empty batch should be completed immediately.
+    #[test]
+    fn test_future_producer_send_all_empty() {
+        let producer = ClientConfig::new().create::<FutureProducer<_>>().unwrap();
+        let results = producer.send_all::<OwnedMessage>(&[]).wait().unwrap();
+        assert!(results.is_empty());
+    }
 }
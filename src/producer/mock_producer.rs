@@ -0,0 +1,209 @@
+//! In-memory producer, for unit testing code that produces to Kafka.
+use error::KafkaResult;
+use message::{Message, OwnedMessage, Timestamp, ToBytes};
+use producer::{AsyncProducer, BatchDeliveryFuture, DeliveryFuture};
+
+use std::sync::{Arc, Mutex};
+
+/// A producer that doesn't connect to any broker, and records every message sent through it
+/// instead. Every message is immediately reported as delivered, to partition 0 unless a partition
+/// is specified, and with offsets starting from 0 for each topic partition.
+///
+/// The `MockProducer` implements `AsyncProducer`, so code that is generic over it can be tested
+/// without a Kafka cluster. Cloning a `MockProducer` returns a handle to the same record of sent
+/// messages.
+#[derive(Clone, Default)]
+pub struct MockProducer {
+    messages: Arc<Mutex<Vec<OwnedMessage>>>,
+}
+
+impl MockProducer {
+    /// Creates a new `MockProducer` with no recorded messages.
+    pub fn new() -> MockProducer {
+        MockProducer::default()
+    }
+
+    /// Records a copy of the payload and key provided. Returns a `DeliveryFuture` that is
+    /// already completed with the partition and offset assigned to the message.
+    pub fn send_copy<P, K>(
+        &self,
+        topic: &str,
+        partition: Option<i32>,
+        payload: Option<&P>,
+        key: Option<&K>,
+        timestamp: Option<i64>
+    ) -> DeliveryFuture
+        where K: ToBytes + ?Sized,
+              P: ToBytes + ?Sized {
+        let timestamp = timestamp.map(|millis| Timestamp::CreateTime(millis)).unwrap_or(Timestamp::NotAvailable);
+        let result = self.record(topic, partition, payload.map(P::to_bytes), key.map(K::to_bytes), timestamp);
+        DeliveryFuture::completed(Ok(result))
+    }
+
+    /// Records a copy of all the provided messages, in order. Messages with a negative partition
+    /// are recorded in partition 0. Returns a `BatchDeliveryFuture` that is already completed
+    /// with the partition and offset assigned to each message.
+    pub fn send_all<M: Message>(&self, messages: &[M]) -> BatchDeliveryFuture {
+        let results = messages.iter()
+            .map(|message| {
+                let partition = if message.partition() >= 0 { Some(message.partition()) } else { None };
+                Ok(self.record(&message.topic(), partition, message.payload(), message.key(), message.timestamp()))
+            })
+            .collect();
+        BatchDeliveryFuture::completed(results)
+    }
+
+    /// Does nothing, since messages are delivered as soon as they are sent.
+    pub fn flush(&self, _timeout_ms: i32) {}
+
+    /// Reports no message in flight to `progress` once, and returns immediately, since messages
+    /// are delivered as soon as they are sent.
+    pub fn flush_with_progress<F: FnMut(i32)>(&self, _timeout_ms: i32, _interval_ms: i32, mut progress: F) -> KafkaResult<()> {
+        progress(0);
+        Ok(())
+    }
+
+    /// Always returns 0, since messages are delivered as soon as they are sent.
+    pub fn in_flight_count(&self) -> i32 {
+        0
+    }
+
+    /// Records a message and returns the partition and offset assigned to it.
+    fn record(&self, topic: &str, partition: Option<i32>, payload: Option<&[u8]>, key: Option<&[u8]>,
+              timestamp: Timestamp) -> (i32, i64) {
+        let partition = partition.unwrap_or(0);
+        let mut messages = self.messages.lock().unwrap();
+        let offset = messages.iter()
+            .filter(|m| m.topic() == topic && m.partition() == partition)
+            .count() as i64;
+        messages.push(OwnedMessage::new(
+            payload.map(|p| p.to_vec()),
+            key.map(|k| k.to_vec()),
+            topic.to_owned(),
+            timestamp,
+            partition,
+            offset
+        ));
+        (partition, offset)
+    }
+
+    /// Returns a copy of all the messages sent so far, in the order they were sent.
+    pub fn sent_messages(&self) -> Vec<OwnedMessage> {
+        self.messages.lock().unwrap().clone()
+    }
+
+    /// Removes all the recorded messages. Offsets will start again from 0.
+    pub fn clear(&self) {
+        self.messages.lock().unwrap().clear();
+    }
+}
+
+impl AsyncProducer for MockProducer {
+    fn send_copy<P, K>(
+        &self,
+        topic: &str,
+        partition: Option<i32>,
+        payload: Option<&P>,
+        key: Option<&K>,
+        timestamp: Option<i64>
+    ) -> DeliveryFuture
+        where K: ToBytes + ?Sized,
+              P: ToBytes + ?Sized {
+        MockProducer::send_copy(self, topic, partition, payload, key, timestamp)
+    }
+
+    fn send_all<M: Message>(&self, messages: &[M]) -> BatchDeliveryFuture {
+        MockProducer::send_all(self, messages)
+    }
+
+    fn flush(&self, timeout_ms: i32) {
+        MockProducer::flush(self, timeout_ms)
+    }
+
+    fn flush_with_progress<F: FnMut(i32)>(&self, timeout_ms: i32, interval_ms: i32, progress: F) -> KafkaResult<()> {
+        MockProducer::flush_with_progress(self, timeout_ms, interval_ms, progress)
+    }
+
+    fn in_flight_count(&self) -> i32 {
+        MockProducer::in_flight_count(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use consumer::MockConsumer;
+    use futures::Future;
+
+    fn produce_greetings<P: AsyncProducer>(producer: &P, names: &[&str]) -> Vec<DeliveryFuture> {
+        names.iter()
+            .map(|name| producer.send_copy("greetings", None, Some(&format!("Hello {}", name)), Some(*name), None))
+            .collect()
+    }
+
+    #[test]
+    fn test_mock_producer_records_messages() {
+        let producer = MockProducer::new();
+        let results = produce_greetings(&producer, &["alice", "bob"])
+            .into_iter()
+            .map(|f| f.wait().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(results[0].as_ref().unwrap(), &(0, 0));
+        assert_eq!(results[1].as_ref().unwrap(), &(0, 1));
+
+        let messages = producer.sent_messages();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].topic(), "greetings");
+        assert_eq!(messages[1].payload_view::<str>(), Some(Ok("Hello bob")));
+        assert_eq!(messages[1].key(), Some("bob".as_bytes()));
+        assert_eq!(messages[1].timestamp(), Timestamp::NotAvailable);
+    }
+
+    #[test]
+    fn test_mock_producer_offsets_per_partition() {
+        let producer = MockProducer::new();
+        let clone = producer.clone();
+        producer.send_copy::<str, str>("topic", Some(3), Some("a"), None, Some(1234));
+        clone.send_copy::<str, str>("topic", Some(3), Some("b"), None, None);
+        let result = producer.send_copy::<str, str>("topic", Some(1), Some("c"), None, None).wait();
+        assert_eq!(result.unwrap().unwrap(), (1, 0));
+
+        let messages = clone.sent_messages();
+        assert_eq!(messages.iter().map(|m| m.offset()).collect::<Vec<_>>(), vec![0, 1, 0]);
+        assert_eq!(messages[0].timestamp(), Timestamp::CreateTime(1234));
+
+        producer.clear();
+        assert!(clone.sent_messages().is_empty());
+    }
+
+    #[test]
+    fn test_mock_producer_send_all_and_flush() {
+        let producer = MockProducer::new();
+        producer.send_copy::<str, str>("topic", Some(0), Some("a"), None, None);
+        let batch = vec![
+            OwnedMessage::new(Some(b"b".to_vec()), None, "topic".to_owned(), Timestamp::CreateTime(10), 0, 0),
+            OwnedMessage::new(Some(b"c".to_vec()), None, "topic".to_owned(), Timestamp::NotAvailable, -1, 0),
+        ];
+        let results = AsyncProducer::send_all(&producer, &batch).wait().unwrap();
+        assert_eq!(results.into_iter().map(|r| r.unwrap()).collect::<Vec<_>>(), vec![(0, 1), (0, 2)]);
+        assert_eq!(producer.sent_messages()[1].timestamp(), Timestamp::CreateTime(10));
+
+        let mut in_flight = Vec::new();
+        AsyncProducer::flush(&producer, 1000);
+        AsyncProducer::flush_with_progress(&producer, 1000, 100, |count| in_flight.push(count)).unwrap();
+        assert_eq!(in_flight, vec![0]);
+        assert_eq!(AsyncProducer::in_flight_count(&producer), 0);
+    }
+
+    // A fixed timestamp should be preserved from the producer to the consumer.
+    #[test]
+    fn test_mock_producer_timestamp_round_trip() {
+        let producer = MockProducer::new();
+        producer.send_copy::<str, str>("events", Some(0), Some("event"), None, Timestamp::CreateTime(1000).to_millis());
+
+        let consumer = MockConsumer::new(producer.sent_messages());
+        let message = consumer.poll(0).unwrap().unwrap();
+        assert_eq!(message.timestamp(), Timestamp::CreateTime(1000));
+        assert_eq!(message.timestamp_millis(), Some(1000));
+    }
+}
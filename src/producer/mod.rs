@@ -46,6 +46,11 @@
 //! to the returned future, and it will executed by the future executor once the value is available
 //! (for more information, check the documentation of the futures crate).
 //!
+//! ### Mock producer
+//! The `MockProducer` doesn't connect to any broker: it records every message sent through it,
+//! and completes the returned future immediately. Code written against the `AsyncProducer` trait
+//! can use the `FutureProducer` in production and the `MockProducer` in unit tests.
+//!
 //! ## Configuration
 //!
 //! ### Producer configuration
@@ -56,7 +61,7 @@
 //!
 //! - `queue.buffering.max.messages` (100000): Maximum number of messages allowed on the producer queue.
 //! - `queue.buffering.max.kbytes` (4000000): Maximum total message size sum allowed on the producer queue. This property has higher priority than queue.buffering.max.messages.
-//! - `queue.buffering.max.ms` (0): Delay in milliseconds to wait for messages in the producer queue to accumulate before sending a request to the brokers. A higher value allows larger and more effective (less overhead, improved compression) batches of messages to accumulate at the expense of increased message delivery latency.
+//! - `queue.buffering.max.ms` (0): Delay in milliseconds to wait for messages in the producer queue to accumulate before sending a request to the brokers. A higher value allows larger and more effective (less overhead, improved compression) batches of messages to accumulate at the expense of increased message delivery latency. It can be set with `ClientConfig::set_linger`.
 //! - `message.send.max.retries` (2): How many times to retry sending a failing MessageSet. Note: retrying may cause reordering.
 //! - `compression.codec` (none): Compression codec to use for compressing message sets.
 //!
@@ -69,14 +74,60 @@
 //! - `message.timeout.ms` (300000): Local message timeout. This value is only enforced locally and limits the time a produced message waits for successful delivery. A time of 0 is infinite.
 //! - `produce.offset.report` (false): Report offset of produced message back to application.
 //!
+//! The topic configuration set with `ClientConfig::set_default_topic_config` applies to all topics.
+//! A different configuration can be used for specific topics with `ClientConfig::set_topic_config`.
+//!
 
 pub mod base_producer;
 pub mod future_producer;
+pub mod mock_producer;
 
 pub use self::base_producer::{
     BaseProducer,
+    BatchRecord,
     DeliveryResult,
     EmptyProducerContext,
     ProducerContext,
 };
-pub use self::future_producer::FutureProducer;
+pub use self::future_producer::{BatchDeliveryFuture, DeliveryFuture, FutureProducer};
+pub use self::mock_producer::MockProducer;
+
+use error::KafkaResult;
+use message::{Message, ToBytes};
+
+/// Common interface of the producers that return a `DeliveryFuture` for each message sent.
+/// User code can be generic over this trait to be tested using a `MockProducer`.
+pub trait AsyncProducer {
+    /// Sends a copy of the payload and key provided to the specified topic, returning a future
+    /// that will be completed with the delivery result. The timestamp, in milliseconds since the
+    /// epoch, is stored as the `CreateTime` of the message, unless the topic is configured to use
+    /// `LogAppendTime`: passing a fixed timestamp makes the tests of event time based processing
+    /// deterministic. If no timestamp is specified, the Kafka producers use the current time.
+    fn send_copy<P, K>(
+        &self,
+        topic: &str,
+        partition: Option<i32>,
+        payload: Option<&P>,
+        key: Option<&K>,
+        timestamp: Option<i64>
+    ) -> DeliveryFuture
+        where K: ToBytes + ?Sized,
+              P: ToBytes + ?Sized;
+
+    /// Sends a copy of all the provided messages, returning a single future that will be
+    /// completed with the delivery result of every message, in the same order.
+    fn send_all<M: Message>(&self, messages: &[M]) -> BatchDeliveryFuture;
+
+    /// Flushes the producer, waiting until all the queued messages are delivered or the timeout
+    /// expires.
+    fn flush(&self, timeout_ms: i32);
+
+    /// Flushes the producer, calling `progress` with the number of messages still in flight
+    /// every `interval_ms` milliseconds. An error is returned if the timeout expires before all
+    /// the messages are delivered.
+    fn flush_with_progress<F: FnMut(i32)>(&self, timeout_ms: i32, interval_ms: i32, progress: F) -> KafkaResult<()>;
+
+    /// Returns the number of messages and requests waiting to be sent to, or acknowledged by,
+    /// the broker.
+    fn in_flight_count(&self) -> i32;
+}
@@ -1,6 +1,11 @@
 use std::collections::HashMap;
 
-#[derive(Deserialize,Debug)]
+/// The statistics of a librdkafka client, as reported every `statistics.interval.ms`. Fields
+/// missing from the JSON emitted by librdkafka are set to their default value, and unknown fields
+/// are ignored, so that the statistics can still be parsed when the format changes across
+/// librdkafka versions.
+#[derive(Deserialize,Debug,Default)]
+#[serde(default)]
 pub struct Statistics {
     pub name: String,
     #[serde(rename = "type")]
@@ -18,7 +23,8 @@ pub struct Statistics {
     pub cgrp: Option<ConsumerGroup>
 }
 
-#[derive(Deserialize,Debug)]
+#[derive(Deserialize,Debug,Default)]
+#[serde(default)]
 pub struct Broker {
     pub name: String,
     pub nodeid: i32,
@@ -47,29 +53,82 @@ pub struct Broker {
     pub toppars: HashMap<String, TopicPartition>
 }
 
-#[derive(Deserialize,Debug)]
+impl Broker {
+    /// Returns the state of the connection to the broker.
+    pub fn broker_state(&self) -> BrokerState {
+        BrokerState::from_stats_value(&self.state)
+    }
+}
+
+/// The state of the connection to a broker, as reported in the statistics.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BrokerState {
+    Init,
+    Down,
+    Connect,
+    Auth,
+    Up,
+    Update,
+    ApiVersionQuery,
+    AuthHandshake,
+    /// A state not known to this version of the library.
+    Unknown,
+}
+
+impl BrokerState {
+    fn from_stats_value(state: &str) -> BrokerState {
+        match state {
+            "INIT" => BrokerState::Init,
+            "DOWN" => BrokerState::Down,
+            "CONNECT" => BrokerState::Connect,
+            "AUTH" => BrokerState::Auth,
+            "UP" => BrokerState::Up,
+            "UPDATE" => BrokerState::Update,
+            "APIVERSION_QUERY" => BrokerState::ApiVersionQuery,
+            "AUTH_HANDSHAKE" => BrokerState::AuthHandshake,
+            _ => BrokerState::Unknown,
+        }
+    }
+}
+
+/// Rolling window statistics, such as the internal latency (`int_latency`) and the round trip
+/// time (`rtt`) of a broker. Values are in microseconds. The standard deviation and the
+/// percentiles are only reported by librdkafka versions that keep an histogram of the window, and
+/// are `None` otherwise.
+#[derive(Deserialize,Debug,Default)]
+#[serde(default)]
 pub struct Window {
     pub min: i64,
     pub max: i64,
     pub avg: i64,
     pub sum: i64,
-    pub cnt: i64
+    pub cnt: i64,
+    pub stddev: Option<i64>,
+    pub p50: Option<i64>,
+    pub p75: Option<i64>,
+    pub p90: Option<i64>,
+    pub p95: Option<i64>,
+    pub p99: Option<i64>,
+    pub p99_99: Option<i64>,
 }
 
-#[derive(Deserialize,Debug)]
+#[derive(Deserialize,Debug,Default)]
+#[serde(default)]
 pub struct TopicPartition {
     pub topic: String,
     pub partition: i32
 }
 
-#[derive(Deserialize,Debug)]
+#[derive(Deserialize,Debug,Default)]
+#[serde(default)]
 pub struct Topic {
     pub topic: String,
     pub metadata_age: i64,
     pub partitions: HashMap<i32, Partition>
 }
 
-#[derive(Deserialize,Debug)]
+#[derive(Deserialize,Debug,Default)]
+#[serde(default)]
 pub struct Partition {
     pub partition: i32,
     pub leader: i32,
@@ -90,20 +149,183 @@ pub struct Partition {
     pub eof_offset: i64,
     pub lo_offset: i64,
     pub hi_offset: i64,
-    pub consumer_lag: i64,
+    /// The consumer lag, or `None` if it's not reported by librdkafka. Use `lag()` to also
+    /// discard the unknown lags reported as -1.
+    pub consumer_lag: Option<i64>,
+    /// The lag computed from the stored offset instead of the committed one. Only reported by
+    /// newer versions of librdkafka.
+    pub consumer_lag_stored: Option<i64>,
     pub txmsgs: i64,
     pub txbytes: i64,
     pub msgs: i64,
     pub rx_ver_drops: i64
 }
 
-#[derive(Deserialize,Debug)]
+impl Partition {
+    /// Returns the consumer lag of the partition, or `None` if the lag is unknown, for instance
+    /// because no offset has been committed yet, or `None` if it's not reported by librdkafka.
+    pub fn lag(&self) -> Option<i64> {
+        self.consumer_lag.and_then(valid_lag)
+    }
+
+    /// Returns the consumer lag computed from the stored offset, or `None` if it's unknown or not
+    /// reported by librdkafka.
+    pub fn stored_lag(&self) -> Option<i64> {
+        self.consumer_lag_stored.and_then(valid_lag)
+    }
+}
+
+/// librdkafka reports -1 as lag when the lag is unknown.
+fn valid_lag(lag: i64) -> Option<i64> {
+    if lag >= 0 {
+        Some(lag)
+    } else {
+        None
+    }
+}
+
+#[derive(Deserialize,Debug,Default)]
+#[serde(default)]
 pub struct ConsumerGroup {
     pub rebalance_age: i64,
     pub rebalance_cnt: i64,
     pub assignment_size: i32
 }
 
+/// The difference between two `Statistics` of the same client, computed with
+/// `Statistics::delta_from`. Counters are converted to the amount accumulated during the interval.
+#[derive(Debug, PartialEq, Eq, Default)]
+pub struct StatisticsDelta {
+    /// Length of the interval, in microseconds.
+    pub interval_us: i64,
+    /// Per broker counters, indexed by broker name.
+    pub brokers: HashMap<String, BrokerDelta>,
+    /// Per partition counters, indexed by topic and partition.
+    pub partitions: HashMap<(String, i32), PartitionDelta>,
+}
+
+/// Broker counters accumulated during an interval.
+#[derive(Debug, PartialEq, Eq, Default)]
+pub struct BrokerDelta {
+    pub tx: i64,
+    pub txbytes: i64,
+    pub txerrs: i64,
+    pub txretries: i64,
+    pub req_timeouts: i64,
+    pub rx: i64,
+    pub rxbytes: i64,
+    pub rxerrs: i64,
+}
+
+/// Partition counters accumulated during an interval.
+#[derive(Debug, PartialEq, Eq, Default)]
+pub struct PartitionDelta {
+    pub txmsgs: i64,
+    pub txbytes: i64,
+    pub msgs: i64,
+}
+
+// A counter lower than its previous value was reset, for example because the broker connection
+// was recreated: the whole value was accumulated during the interval.
+fn counter_delta(current: i64, previous: i64) -> i64 {
+    if current >= previous {
+        current - previous
+    } else {
+        current
+    }
+}
+
+impl Statistics {
+    /// Returns the id of the broker each partition is currently handled by, indexed by topic and
+    /// partition. For consumers, this is the broker the partition is fetched from. Partitions not
+    /// handled by any broker, for instance while the leader is unknown, are not included.
+    pub fn partition_brokers(&self) -> HashMap<(String, i32), i32> {
+        let mut partition_brokers = HashMap::new();
+        for broker in self.brokers.values() {
+            for toppar in broker.toppars.values() {
+                partition_brokers.insert((toppar.topic.clone(), toppar.partition), broker.nodeid);
+            }
+        }
+        partition_brokers
+    }
+
+    /// Returns the consumer lag of every partition whose lag is known, indexed by topic and
+    /// partition. The internal partition used by librdkafka for unassigned messages is excluded.
+    pub fn lag_per_partition(&self) -> HashMap<(String, i32), i64> {
+        let mut lags = HashMap::new();
+        for (topic_name, topic) in &self.topics {
+            for (&id, partition) in &topic.partitions {
+                if id < 0 {
+                    continue;
+                }
+                if let Some(lag) = partition.lag() {
+                    lags.insert((topic_name.clone(), id), lag);
+                }
+            }
+        }
+        lags
+    }
+
+    /// Returns the counters accumulated since the `previous` statistics of the same client, to be
+    /// used to compute per-interval rates. Brokers and partitions that are not present in the
+    /// previous statistics are reported with their whole counter values.
+    pub fn delta_from(&self, previous: &Statistics) -> StatisticsDelta {
+        let brokers = self.brokers.iter()
+            .map(|(name, broker)| {
+                let delta = match previous.brokers.get(name) {
+                    Some(prev) => BrokerDelta {
+                        tx: counter_delta(broker.tx, prev.tx),
+                        txbytes: counter_delta(broker.txbytes, prev.txbytes),
+                        txerrs: counter_delta(broker.txerrs, prev.txerrs),
+                        txretries: counter_delta(broker.txretries, prev.txretries),
+                        req_timeouts: counter_delta(broker.req_timeouts, prev.req_timeouts),
+                        rx: counter_delta(broker.rx, prev.rx),
+                        rxbytes: counter_delta(broker.rxbytes, prev.rxbytes),
+                        rxerrs: counter_delta(broker.rxerrs, prev.rxerrs),
+                    },
+                    None => BrokerDelta {
+                        tx: broker.tx,
+                        txbytes: broker.txbytes,
+                        txerrs: broker.txerrs,
+                        txretries: broker.txretries,
+                        req_timeouts: broker.req_timeouts,
+                        rx: broker.rx,
+                        rxbytes: broker.rxbytes,
+                        rxerrs: broker.rxerrs,
+                    },
+                };
+                (name.clone(), delta)
+            })
+            .collect();
+
+        let mut partitions = HashMap::new();
+        for (topic_name, topic) in &self.topics {
+            for (&id, partition) in &topic.partitions {
+                let prev = previous.topics.get(topic_name).and_then(|t| t.partitions.get(&id));
+                let delta = match prev {
+                    Some(prev) => PartitionDelta {
+                        txmsgs: counter_delta(partition.txmsgs, prev.txmsgs),
+                        txbytes: counter_delta(partition.txbytes, prev.txbytes),
+                        msgs: counter_delta(partition.msgs, prev.msgs),
+                    },
+                    None => PartitionDelta {
+                        txmsgs: partition.txmsgs,
+                        txbytes: partition.txbytes,
+                        msgs: partition.msgs,
+                    },
+                };
+                partitions.insert((topic_name.clone(), id), delta);
+            }
+        }
+
+        StatisticsDelta {
+            interval_us: self.ts - previous.ts,
+            brokers,
+            partitions,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use serde_json;
@@ -126,6 +348,105 @@ mod tests {
 
         assert_eq!(stats.brokers.len(), 4);
         assert_eq!(stats.topics.len(), 1);
+
+        assert_eq!(stats.brokers["localhost:9092/2"].broker_state(), BrokerState::Up);
+
+        let throttle = stats.brokers["localhost:9092/2"].throttle.as_ref().unwrap();
+        assert_eq!(throttle.max, 0);
+        assert_eq!(throttle.cnt, 19);
+        assert_eq!(throttle.p99, None);
+    }
+
+    #[test]
+    fn test_statistics_partition_brokers() {
+        let stats: Statistics = serde_json::from_str(EXAMPLE).unwrap();
+        let partition_brokers = stats.partition_brokers();
+        assert_eq!(partition_brokers.len(), 4);
+        assert_eq!(partition_brokers[&("test".to_owned(), 0)], 4);
+        assert_eq!(partition_brokers[&("test".to_owned(), 1)], 2);
+        assert_eq!(partition_brokers[&("test".to_owned(), 2)], 3);
+        assert_eq!(partition_brokers[&("test".to_owned(), 3)], 4);
+    }
+
+    #[test]
+    fn test_statistics_lag_per_partition() {
+        let mut stats: Statistics = serde_json::from_str(EXAMPLE).unwrap();
+        {
+            let partitions = &mut stats.topics.get_mut("test").unwrap().partitions;
+            partitions.get_mut(&1).unwrap().consumer_lag = Some(42);
+            partitions.get_mut(&2).unwrap().consumer_lag = Some(-1);
+        }
+
+        let lags = stats.lag_per_partition();
+        assert_eq!(lags.len(), 3);
+        assert_eq!(lags[&("test".to_owned(), 0)], 0);
+        assert_eq!(lags[&("test".to_owned(), 1)], 42);
+        assert_eq!(lags[&("test".to_owned(), 3)], 0);
+        assert_eq!(stats.topics["test"].partitions[&2].lag(), None);
+        assert_eq!(stats.topics["test"].partitions[&1].stored_lag(), None);
+    }
+
+    #[test]
+    fn test_statistics_missing_and_unknown_fields() {
+        let stats: Statistics = serde_json::from_str(r#"
+            {
+              "name": "rdkafka#producer-1", "type": "producer", "new_field": {"value": 1},
+              "brokers": {"localhost:9092/0": {"name": "localhost:9092/0", "state": "UP", "new_counter": 12}}
+            }"#).unwrap();
+        assert_eq!(stats.name, "rdkafka#producer-1");
+        assert_eq!(stats.msg_cnt, 0);
+        assert_eq!(stats.brokers["localhost:9092/0"].broker_state(), BrokerState::Up);
+        assert_eq!(stats.brokers["localhost:9092/0"].tx, 0);
+        assert!(stats.topics.is_empty());
+        assert!(stats.cgrp.is_none());
+    }
+
+    #[test]
+    fn test_statistics_missing_consumer_lag() {
+        let stats: Statistics = serde_json::from_str(r#"
+            {
+              "name": "rdkafka#producer-1", "type": "producer",
+              "topics": {"test": {"topic": "test", "partitions": {"0": {"partition": 0, "leader": 1}}}}
+            }"#).unwrap();
+        let partition = &stats.topics["test"].partitions[&0];
+        assert_eq!(partition.consumer_lag, None);
+        assert_eq!(partition.lag(), None);
+        assert!(stats.lag_per_partition().is_empty());
+    }
+
+    #[test]
+    fn test_statistics_window_percentiles() {
+        let window: Window = serde_json::from_str(r#"
+            {
+              "min": 10, "max": 900, "avg": 120, "sum": 1200, "cnt": 10, "stddev": 30,
+              "hdrsize": 11376, "p50": 100, "p75": 130, "p90": 200, "p95": 400, "p99": 900,
+              "p99_99": 900, "outofrange": 0
+            }"#).unwrap();
+        assert_eq!(window.avg, 120);
+        assert_eq!(window.stddev, Some(30));
+        assert_eq!(window.p50, Some(100));
+        assert_eq!(window.p95, Some(400));
+        assert_eq!(window.p99_99, Some(900));
+    }
+
+    #[test]
+    fn test_statistics_delta() {
+        let previous: Statistics = serde_json::from_str(EXAMPLE).unwrap();
+        let mut current: Statistics = serde_json::from_str(EXAMPLE).unwrap();
+        current.ts += 1000000;
+        current.brokers.get_mut("localhost:9092/2").unwrap().tx += 10;
+        current.brokers.get_mut("0:9092/bootstrap").unwrap().rx = 1;
+        current.topics.get_mut("test").unwrap().partitions.get_mut(&0).unwrap().msgs += 5;
+
+        let delta = current.delta_from(&previous);
+        assert_eq!(delta.interval_us, 1000000);
+        assert_eq!(delta.brokers["localhost:9092/2"].tx, 10);
+        assert_eq!(delta.brokers["localhost:9092/2"].rx, 0);
+        assert_eq!(delta.brokers["0:9092/bootstrap"].rx, 1);
+        assert_eq!(delta.partitions[&("test".to_owned(), 0)].msgs, 5);
+        assert_eq!(delta.partitions[&("test".to_owned(), -1)], PartitionDelta::default());
+
+        assert_eq!(previous.delta_from(&previous).brokers["localhost:9092/2"], BrokerDelta::default());
     }
 
     // Example from https://github.com/edenhill/librdkafka/wiki/Statistics
@@ -4,9 +4,11 @@ use rdsys;
 use rdsys::types::*;
 
 use error::{IsError, KafkaError, KafkaResult};
+use util::kafka_cstr;
 
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::ffi::{CStr, CString};
+use std::ffi::CString;
 use std::fmt;
 use std::ptr;
 use std::slice;
@@ -17,6 +19,7 @@ const OFFSET_BEGINNING: i64 = rdsys::RD_KAFKA_OFFSET_BEGINNING as i64;
 const OFFSET_END: i64 = rdsys::RD_KAFKA_OFFSET_END as i64;
 const OFFSET_STORED: i64 = rdsys::RD_KAFKA_OFFSET_STORED as i64;
 const OFFSET_INVALID: i64 = rdsys::RD_KAFKA_OFFSET_INVALID as i64;
+const OFFSET_TAIL_BASE: i64 = rdsys::RD_KAFKA_OFFSET_TAIL_BASE as i64;
 
 /// A librdkafka offset.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -30,17 +33,22 @@ pub enum Offset {
     /// Offset not assigned or invalid.
     Invalid,
     /// A specific offset to consume from.
-    Offset(i64)
+    Offset(i64),
+    /// Start consuming the specified number of messages before the end of the partition.
+    OffsetTail(i64),
 }
 
 impl Offset {
-    /// Converts the integer representation of an offset use by librdkafka to an `Offset`.
+    /// Converts the integer representation of an offset use by librdkafka to an `Offset`,
+    /// including the special values used for the beginning, the end, the stored offset, the
+    /// invalid offset and the tail offsets. Useful to restore offsets saved with `to_raw`.
     pub fn from_raw(raw_offset: i64) -> Offset {
         match raw_offset {
             OFFSET_BEGINNING => Offset::Beginning,
             OFFSET_END => Offset::End,
             OFFSET_STORED => Offset::Stored,
             OFFSET_INVALID => Offset::Invalid,
+            n if n <= OFFSET_TAIL_BASE => Offset::OffsetTail(OFFSET_TAIL_BASE - n),
             n => Offset::Offset(n)
         }
     }
@@ -53,6 +61,7 @@ impl Offset {
             Offset::Stored => OFFSET_STORED,
             Offset::Invalid => OFFSET_INVALID,
             Offset::Offset(n) => n,
+            Offset::OffsetTail(n) => OFFSET_TAIL_BASE - n,
         }
     }
 }
@@ -72,12 +81,10 @@ impl<'a> TopicPartitionListElem<'a> {
         }
     }
 
-    /// Returns the topic name.
-    pub fn topic(&self) -> &str {
-        unsafe {
-            let c_str = (*self.ptr).topic;
-            CStr::from_ptr(c_str).to_str().expect("Topic name is not UTF-8")
-        }
+    /// Returns the topic name. Invalid UTF-8 sequences are replaced with the replacement
+    /// character.
+    pub fn topic(&self) -> Cow<str> {
+        unsafe { kafka_cstr((*self.ptr).topic) }
     }
 
     /// Returns the optional error associated to the specific entry in the TPL.
@@ -270,9 +277,41 @@ impl TopicPartitionList {
     /// Returns a hashmap-based representation of the list.
     pub fn to_topic_map(&self) -> HashMap<(String, i32), Offset> {
         self.elements().iter()
-            .map(|elem| ((elem.topic().to_owned(), elem.partition()), elem.offset()))
+            .map(|elem| ((elem.topic().into_owned(), elem.partition()), elem.offset()))
             .collect()
     }
+
+    /// Compares the topic partitions in this list with the ones in `other`, and returns a tuple
+    /// containing the list of topic partitions that are only present in this list (added), and the
+    /// list of topic partitions that are only present in `other` (removed). Offsets are not
+    /// compared, and are copied from the list the element belongs to. The order of the elements
+    /// doesn't affect the result.
+    pub fn difference(&self, other: &TopicPartitionList) -> (TopicPartitionList, TopicPartitionList) {
+        (self.subtract(other), other.subtract(self))
+    }
+
+    /// Returns the list of topic partitions present both in this list and in `other`. Offsets are
+    /// copied from this list.
+    pub fn intersection(&self, other: &TopicPartitionList) -> TopicPartitionList {
+        let mut result = TopicPartitionList::new();
+        for elem in self.elements() {
+            if other.find_partition(&elem.topic(), elem.partition()).is_some() {
+                result.add_partition_offset(&elem.topic(), elem.partition(), elem.offset());
+            }
+        }
+        result
+    }
+
+    /// Returns the list of topic partitions present in this list but not in `other`.
+    fn subtract(&self, other: &TopicPartitionList) -> TopicPartitionList {
+        let mut result = TopicPartitionList::new();
+        for elem in self.elements() {
+            if other.find_partition(&elem.topic(), elem.partition()).is_none() {
+                result.add_partition_offset(&elem.topic(), elem.partition(), elem.offset());
+            }
+        }
+        result
+    }
 }
 
 impl Drop for TopicPartitionList {
@@ -292,7 +331,7 @@ impl PartialEq for TopicPartitionList {
         }
         self.elements().iter()
             .all(|elem| {
-                if let Some(other_elem) = other.find_partition(elem.topic(), elem.partition()) {
+                if let Some(other_elem) = other.find_partition(&elem.topic(), elem.partition()) {
                     elem == &other_elem
                 } else {
                     false
@@ -426,4 +465,42 @@ mod tests {
         assert_eq!(topic_map, topic_map2);
         assert_eq!(tpl, tpl2);
     }
+
+    #[test]
+    fn test_difference_intersection() {
+        let mut old_tpl = TopicPartitionList::new();
+        old_tpl.add_partition_offset("topic1", 0, Offset::Offset(10));
+        old_tpl.add_partition_offset("topic1", 1, Offset::Offset(11));
+        old_tpl.add_partition_offset("topic2", 0, Offset::Offset(20));
+
+        let mut new_tpl = TopicPartitionList::new();
+        new_tpl.add_partition_offset("topic2", 1, Offset::Beginning);
+        new_tpl.add_partition_offset("topic2", 0, Offset::Invalid);
+        new_tpl.add_partition_offset("topic1", 1, Offset::Invalid);
+
+        let (added, removed) = new_tpl.difference(&old_tpl);
+
+        let mut expected_added = TopicPartitionList::new();
+        expected_added.add_partition_offset("topic2", 1, Offset::Beginning);
+        let mut expected_removed = TopicPartitionList::new();
+        expected_removed.add_partition_offset("topic1", 0, Offset::Offset(10));
+        assert_eq!(added, expected_added);
+        assert_eq!(removed, expected_removed);
+
+        let mut expected_intersection = TopicPartitionList::new();
+        expected_intersection.add_partition_offset("topic1", 1, Offset::Invalid);
+        expected_intersection.add_partition_offset("topic2", 0, Offset::Invalid);
+        assert_eq!(new_tpl.intersection(&old_tpl), expected_intersection);
+    }
+
+    #[test]
+    fn test_offset_raw_conversion() {
+        let offsets = vec![Offset::Beginning, Offset::End, Offset::Stored, Offset::Invalid,
+                           Offset::Offset(0), Offset::Offset(1234), Offset::OffsetTail(0),
+                           Offset::OffsetTail(10)];
+        for offset in offsets {
+            assert_eq!(Offset::from_raw(offset.to_raw()), offset);
+        }
+        assert_eq!(Offset::OffsetTail(10).to_raw(), -2010);
+    }
 }
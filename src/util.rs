@@ -1,5 +1,8 @@
 use rdsys;
 
+use error::{IsError, KafkaError, KafkaResult};
+
+use std::borrow::Cow;
 use std::ffi::CStr;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
@@ -11,6 +14,19 @@ pub fn get_rdkafka_version() -> (u16, String) {
     (version_number, c_str.to_string_lossy().into_owned())
 }
 
+/// Returns the last error set by librdkafka in the current thread as a `KafkaError::Global`,
+/// or `Ok` if no error is set. Only the librdkafka functions that signal a failure with a
+/// sentinel value, such as a null pointer, set the last error: it can be used to get more details
+/// about their failures.
+pub fn last_error() -> KafkaResult<()> {
+    let error = unsafe { rdsys::rd_kafka_last_error() };
+    if error.is_error() {
+        Err(KafkaError::Global(error.into()))
+    } else {
+        Ok(())
+    }
+}
+
 /// Converts a Duration into milliseconds
 pub fn duration_to_millis(duration: Duration) -> u64 {
     let nanos = duration.subsec_nanos() as u64;
@@ -24,15 +40,26 @@ pub fn millis_to_epoch(time: SystemTime) -> i64 {
 }
 
 
-// TODO: check if the implementation returns a copy of the data and update the documentation
-/// Converts a byte array representing a C string into a String.
+/// Returns a view of a C string owned by librdkafka. A null pointer is converted to an empty
+/// string, and invalid UTF-8 sequences are replaced with the replacement character. No copy is
+/// performed unless the string contains invalid UTF-8. The caller must make sure that the
+/// returned value doesn't outlive the memory pointed by `ptr`.
+pub unsafe fn kafka_cstr<'a>(ptr: *const i8) -> Cow<'a, str> {
+    if ptr.is_null() {
+        Cow::Borrowed("")
+    } else {
+        CStr::from_ptr(ptr).to_string_lossy()
+    }
+}
+
+/// Converts a byte array representing a C string into a String. The data is copied.
 pub unsafe fn bytes_cstr_to_owned(bytes_cstr: &[i8]) -> String {
-    CStr::from_ptr(bytes_cstr.as_ptr()).to_string_lossy().into_owned()
+    kafka_cstr(bytes_cstr.as_ptr()).into_owned()
 }
 
-/// Converts a C string into a String.
+/// Converts a C string into a String. The data is copied.
 pub unsafe fn cstr_to_owned(cstr: *const i8) -> String {
-    CStr::from_ptr(cstr).to_string_lossy().into_owned()
+    kafka_cstr(cstr).into_owned()
 }
 
 #[cfg(test)]
@@ -40,6 +67,24 @@ mod tests {
     use super::*;
     use std::time::Duration;
 
+    #[test]
+    fn test_kafka_cstr() {
+        use std::ptr;
+
+        let valid = b"hello\0";
+        let invalid = b"he\xffllo\0";
+        unsafe {
+            assert_eq!(kafka_cstr(ptr::null()), "");
+            assert_eq!(kafka_cstr(valid.as_ptr() as *const i8), "hello");
+            assert_eq!(kafka_cstr(invalid.as_ptr() as *const i8), "he\u{FFFD}llo");
+        }
+    }
+
+    #[test]
+    fn test_last_error() {
+        assert_eq!(last_error(), Ok(()));
+    }
+
     #[test]
     fn test_duration_to_millis() {
         assert_eq!(duration_to_millis(Duration::from_secs(1)), 1000);
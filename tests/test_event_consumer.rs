@@ -0,0 +1,58 @@
+//! Test the consumption of librdkafka events with the EventConsumer.
+extern crate env_logger;
+extern crate futures;
+extern crate rand;
+extern crate rdkafka;
+
+use rdkafka::Message;
+use rdkafka::client::EmptyContext;
+use rdkafka::config::{ClientConfig, TopicConfig};
+use rdkafka::event::{Event, EventConsumer, RebalanceEvent};
+use rdkafka::types::RDKafkaType;
+
+mod utils;
+use utils::*;
+
+// The assignment should be returned as a rebalance event, instead of going through the context.
+#[test]
+fn test_event_consumer_rebalance() {
+    let _r = env_logger::init();
+
+    let topic_name = rand_test_topic();
+    produce_messages(&topic_name, 1, &value_fn, &key_fn, Some(0), None);
+
+    let mut config = ClientConfig::new();
+    config.set("bootstrap.servers", get_bootstrap_server().as_str());
+    config.set("group.id", &rand_test_group());
+    config.set("session.timeout.ms", "6000");
+    config.set_default_topic_config(
+        TopicConfig::new()
+            .set("auto.offset.reset", "earliest")
+            .finalize()
+    );
+    let consumer = EventConsumer::new(&config, RDKafkaType::RD_KAFKA_CONSUMER, EmptyContext::new())
+        .expect("Consumer creation error");
+    consumer.subscribe(&[topic_name.as_str()]).unwrap();
+
+    let mut assignment = None;
+    for _ in 0..300 {
+        if let Some(Event::Rebalance(RebalanceEvent::Assign(tpl))) = consumer.poll_event(100) {
+            assignment = Some(tpl);
+            break;
+        }
+    }
+    let assignment = assignment.expect("Assignment not received");
+    assert!(assignment.find_partition(&topic_name, 0).is_some());
+    consumer.assign(Some(&assignment)).unwrap();
+
+    let mut message = None;
+    for _ in 0..300 {
+        if let Some(Event::Message(result)) = consumer.poll_event(100) {
+            message = Some(result.expect("Error receiving message"));
+            break;
+        }
+    }
+    let message = message.expect("Message not received");
+    assert_eq!(message.topic(), topic_name.as_str());
+    assert_eq!(message.offset(), 0);
+}
@@ -8,15 +8,22 @@ extern crate rdkafka;
 use futures::*;
 
 use rdkafka::{Message, Timestamp};
-use rdkafka::consumer::{Consumer, CommitMode};
+use rdkafka::message::OwnedMessage;
+use rdkafka::client::Context;
+use rdkafka::config::ClientConfig;
+use rdkafka::consumer::{commit_offsets_for_group, committed_offsets_for_group, BaseConsumer, Consumer, ConsumerContext,
+                        CommitMode};
 use rdkafka::topic_partition_list::{Offset, TopicPartitionList};
-use rdkafka::error::KafkaError;
+use rdkafka::error::{KafkaError, RDKafkaError};
 
 mod utils;
 use utils::*;
 
 use std::time::{Duration, Instant};
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
 
 // All messages should go to the same partition.
 #[test]
@@ -167,7 +174,430 @@ fn test_consume_with_no_message_error() {
     assert!(first_poll_time.unwrap().elapsed() > Duration::from_millis(4500));
 }
 
+// The consumer should keep consuming after being stopped and restarted.
+#[test]
+fn test_consumer_stop_restart() {
+    let _r = env_logger::init();
+
+    let topic_name = rand_test_topic();
+    produce_messages(&topic_name, 100, &value_fn, &key_fn, None, None);
+    let consumer = create_stream_consumer(&rand_test_group(), None);
+    consumer.subscribe(&[topic_name.as_str()]).unwrap();
+
+    let first_count = consumer.start()
+        .take(10)
+        .wait()
+        .filter(|message| match *message {
+            Ok(Ok(_)) => true,
+            _ => false,
+        })
+        .count();
+    assert_eq!(first_count, 10);
+
+    consumer.stop();
+    // Stopping twice has no effect.
+    consumer.stop();
+
+    let second_count = consumer.start()
+        .take(10)
+        .wait()
+        .filter(|message| match *message {
+            Ok(Ok(_)) => true,
+            _ => false,
+        })
+        .count();
+    assert_eq!(second_count, 10);
+}
+
+// The consumer should start from the requested offsets.
+#[test]
+fn test_consumer_subscribe_with_offsets() {
+    let _r = env_logger::init();
+
+    let topic_name = rand_test_topic();
+    produce_messages(&topic_name, 10, &value_fn, &key_fn, Some(0), None);
+    let consumer = create_stream_consumer(&rand_test_group(), None);
+
+    let mut offsets = TopicPartitionList::new();
+    offsets.add_partition_offset(&topic_name, 0, Offset::Offset(5));
+    consumer.subscribe_with_offsets(&offsets).unwrap();
+
+    let received_offsets = consumer.start()
+        .take(5)
+        .wait()
+        .map(|message| match message {
+            Ok(Ok(m)) => m.offset(),
+            e => panic!("Error receiving message: {:?}", e),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(received_offsets, vec![5, 6, 7, 8, 9]);
+}
+
+// Subscribing to a topic that doesn't exist should fail, without changing the subscription.
+#[test]
+fn test_consumer_subscribe_checked() {
+    let _r = env_logger::init();
+
+    let topic_name = rand_test_topic();
+    let missing_topic_name = rand_test_topic();
+    produce_messages(&topic_name, 1, &value_fn, &key_fn, Some(0), None);
+    let consumer = create_stream_consumer(&rand_test_group(), None);
+
+    match consumer.subscribe_checked(&[topic_name.as_str(), missing_topic_name.as_str()], 5000) {
+        Err(KafkaError::Subscription(ref description)) => assert!(description.contains(&missing_topic_name)),
+        result => panic!("Unexpected subscription result: {:?}", result),
+    }
+    assert_eq!(consumer.subscription().unwrap().count(), 0);
+
+    consumer.subscribe_checked(&[topic_name.as_str(), "^missing-.*"], 5000).unwrap();
+    assert_eq!(consumer.subscription().unwrap().count(), 2);
+}
+
+// The stream of the base consumer should be driven by the task consuming it.
+#[test]
+fn test_base_consumer_stream() {
+    let _r = env_logger::init();
+
+    let topic_name = rand_test_topic();
+    produce_messages(&topic_name, 10, &value_fn, &key_fn, Some(0), None);
+    let consumer = create_stream_consumer(&rand_test_group(), None);
+    consumer.subscribe(&[topic_name.as_str()]).unwrap();
+
+    let offsets = consumer.get_base_consumer().stream()
+        .take(10)
+        .wait()
+        .map(|message| match message {
+            Ok(Ok(m)) => m.offset(),
+            e => panic!("Error receiving message: {:?}", e),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(offsets, (0..10).collect::<Vec<_>>());
+}
+
+// Resubscribing should wait for the rebalance, even when the new assignment is empty.
+#[test]
+fn test_consumer_resubscribe() {
+    let _r = env_logger::init();
+
+    let topic_name = rand_test_topic();
+    produce_messages(&topic_name, 1, &value_fn, &key_fn, Some(0), None);
+    let mut config = ClientConfig::new();
+    config.set("bootstrap.servers", get_bootstrap_server().as_str());
+    config.set("group.id", &rand_test_group());
+    config.set("session.timeout.ms", "6000");
+    config.set("enable.auto.commit", "false");
+    let consumer = Arc::new(config.create::<BaseConsumer<_>>().expect("Consumer creation failed"));
+    consumer.subscribe(&[topic_name.as_str()]).unwrap();
+
+    // Rebalances are served by the thread polling the consumer.
+    let stop = Arc::new(AtomicBool::new(false));
+    let poll_handle = {
+        let consumer = consumer.clone();
+        let stop = stop.clone();
+        thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                consumer.poll(100);
+            }
+        })
+    };
+
+    let assignment = consumer.resubscribe(&["^missing-.*", topic_name.as_str()], 30000).unwrap();
+    assert!(assignment.find_partition(&topic_name, 0).is_some());
+
+    let assignment = consumer.resubscribe(&["^missing-.*"], 30000).unwrap();
+    assert_eq!(assignment.count(), 0);
+
+    stop.store(true, Ordering::Relaxed);
+    poll_handle.join().unwrap();
+}
+
+// Offsets can't be stored explicitly when they are stored automatically.
+#[test]
+fn test_consumer_store_offsets_auto_store() {
+    let _r = env_logger::init();
+
+    let topic_name = rand_test_topic();
+    produce_messages(&topic_name, 1, &value_fn, &key_fn, Some(0), None);
+    let consumer = create_stream_consumer(&rand_test_group(), None);
+    let mut assignment = TopicPartitionList::new();
+    assignment.add_partition_offset(&topic_name, 0, Offset::Beginning);
+    consumer.assign(&assignment).unwrap();
+
+    let mut offsets = TopicPartitionList::new();
+    offsets.add_partition_offset(&topic_name, 0, Offset::Offset(1));
+    match consumer.store_offsets(&offsets) {
+        Err(KafkaError::StoreOffset(RDKafkaError::InvalidArgument)) => {},
+        result => panic!("Unexpected store result: {:?}", result),
+    }
+}
+
+// Waiting for the assignment should neither consume nor store any message.
+#[test]
+fn test_consumer_wait_for_assignment() {
+    let _r = env_logger::init();
+
+    let topic_name = rand_test_topic();
+    produce_messages(&topic_name, 10, &value_fn, &key_fn, Some(0), None);
+    let consumer = create_stream_consumer(&rand_test_group(), None);
+    consumer.subscribe(&[topic_name.as_str()]).unwrap();
+
+    let assignment = consumer.wait_for_assignment(30000).unwrap();
+    assert!(assignment.find_partition(&topic_name, 0).is_some());
+
+    let _ = consumer.commit_consumer_state(CommitMode::Sync);
+    let committed = consumer.committed(5000).unwrap();
+    assert_eq!(committed.find_partition(&topic_name, 0).unwrap().offset(), Offset::Invalid);
+
+    let first = consumer.start().take(1).wait().next();
+    match first {
+        Some(Ok(Ok(m))) => assert_eq!(m.offset(), 0),
+        e => panic!("Error receiving message: {:?}", e),
+    }
+}
+
+struct FirstPartitionContext;
+
+impl Context for FirstPartitionContext {}
+
+impl ConsumerContext for FirstPartitionContext {
+    fn override_assignment(&self, proposed: &TopicPartitionList) -> Option<TopicPartitionList> {
+        let mut assignment = TopicPartitionList::new();
+        for elem in proposed.elements().iter().filter(|elem| elem.partition() == 0) {
+            assignment.add_partition_offset(&elem.topic(), 0, elem.offset());
+        }
+        Some(assignment)
+    }
+}
+
+// Only the partitions returned by the context should be assigned and consumed.
+#[test]
+fn test_consumer_override_assignment() {
+    let _r = env_logger::init();
+
+    let topic_name = rand_test_topic();
+    produce_messages(&topic_name, 5, &value_fn, &key_fn, Some(0), None);
+    produce_messages(&topic_name, 5, &value_fn, &key_fn, Some(1), None);
+    let consumer = create_stream_consumer_with_context(&rand_test_group(), None, FirstPartitionContext);
+    consumer.subscribe(&[topic_name.as_str()]).unwrap();
+
+    let partitions = consumer.start()
+        .take(5)
+        .wait()
+        .map(|message| match message {
+            Ok(Ok(m)) => m.partition(),
+            e => panic!("Error receiving message: {:?}", e),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(partitions, vec![0; 5]);
+
+    let mut assignment = TopicPartitionList::new();
+    assignment.add_partition(&topic_name, 0);
+    assert_eq!(consumer.assignment().unwrap(), assignment);
+}
+
+// Seeking multiple partitions should report the error of each partition.
+#[test]
+fn test_consumer_seek_partitions() {
+    let _r = env_logger::init();
+
+    let topic_name = rand_test_topic();
+    produce_messages(&topic_name, 10, &value_fn, &key_fn, Some(0), None);
+    let consumer = create_stream_consumer(&rand_test_group(), None);
+
+    let mut assignment = TopicPartitionList::new();
+    assignment.add_partition_offset(&topic_name, 0, Offset::Beginning);
+    consumer.assign(&assignment).unwrap();
+
+    let _first_message = consumer.start().take(1).wait().next().unwrap();
+
+    let mut offsets = TopicPartitionList::new();
+    offsets.add_partition_offset(&topic_name, 0, Offset::Offset(7));
+    offsets.add_partition_offset(&topic_name, 5, Offset::Offset(7));
+    let results = consumer.seek_partitions(&offsets, 5000).unwrap();
+    assert!(results.find_partition(&topic_name, 0).unwrap().error().is_ok());
+    assert!(results.find_partition(&topic_name, 5).unwrap().error().is_err());
+}
+
+// Polls the base consumer until `count` messages are received or the timeout expires, and returns
+// the partition and offset of each message.
+fn poll_positions<C: ConsumerContext>(consumer: &BaseConsumer<C>, count: usize, timeout: Duration) -> Vec<(i32, i64)> {
+    let start_time = Instant::now();
+    let mut positions = Vec::new();
+    while positions.len() < count && start_time.elapsed() < timeout {
+        match consumer.poll(100) {
+            Some(Ok(m)) => positions.push((m.partition(), m.offset())),
+            Some(Err(KafkaError::PartitionEOF(_))) | None => {},
+            Some(Err(e)) => panic!("Error receiving message: {:?}", e),
+        }
+    }
+    positions
+}
+
+// After seeking, the consumer should return the message at the new offset.
+#[test]
+fn test_consumer_seek() {
+    let _r = env_logger::init();
+
+    let topic_name = rand_test_topic();
+    produce_messages(&topic_name, 10, &value_fn, &key_fn, Some(0), None);
+    let consumer = create_stream_consumer(&rand_test_group(), None);
+
+    let mut assignment = TopicPartitionList::new();
+    assignment.add_partition_offset(&topic_name, 0, Offset::Beginning);
+    consumer.assign(&assignment).unwrap();
+
+    let base_consumer = consumer.get_base_consumer();
+    assert_eq!(poll_positions(base_consumer, 1, Duration::from_secs(30)), vec![(0, 0)]);
+    consumer.seek(&topic_name, 0, Offset::Offset(7), 5000).unwrap();
+    assert_eq!(poll_positions(base_consumer, 3, Duration::from_secs(30)), vec![(0, 7), (0, 8), (0, 9)]);
+
+    // Partitions that don't exist can't be sought.
+    match consumer.seek(&topic_name, 5, Offset::Offset(0), 5000) {
+        Err(KafkaError::Seek(_)) => {},
+        r => panic!("Unexpected seek result: {:?}", r),
+    }
+}
+
+// No message should be returned for a paused partition, until it is resumed.
+#[test]
+fn test_consumer_pause_resume() {
+    let _r = env_logger::init();
+
+    let topic_name = rand_test_topic();
+    produce_messages(&topic_name, 10, &value_fn, &key_fn, Some(0), None);
+    produce_messages(&topic_name, 10, &value_fn, &key_fn, Some(1), None);
+    let consumer = create_stream_consumer(&rand_test_group(), None);
+
+    let mut assignment = TopicPartitionList::new();
+    assignment.add_partition_offset(&topic_name, 0, Offset::Beginning);
+    assignment.add_partition_offset(&topic_name, 1, Offset::Beginning);
+    consumer.assign(&assignment).unwrap();
+
+    let mut paused = TopicPartitionList::new();
+    paused.add_partition(&topic_name, 0);
+    consumer.pause(&paused).unwrap();
+
+    let base_consumer = consumer.get_base_consumer();
+    let positions = poll_positions(base_consumer, 20, Duration::from_secs(10));
+    assert_eq!(positions, (0..10).map(|offset| (1, offset)).collect::<Vec<_>>());
+
+    consumer.resume(&paused).unwrap();
+    let positions = poll_positions(base_consumer, 10, Duration::from_secs(30));
+    assert_eq!(positions, (0..10).map(|offset| (0, offset)).collect::<Vec<_>>());
+}
+
+// A partition paused and sought should restart from the new offset once resumed.
+#[test]
+fn test_consumer_pause_and_seek() {
+    let _r = env_logger::init();
+
+    let topic_name = rand_test_topic();
+    produce_messages(&topic_name, 10, &value_fn, &key_fn, Some(0), None);
+    let consumer = create_stream_consumer(&rand_test_group(), None);
+
+    let mut assignment = TopicPartitionList::new();
+    assignment.add_partition_offset(&topic_name, 0, Offset::Beginning);
+    consumer.assign(&assignment).unwrap();
+
+    let base_consumer = consumer.get_base_consumer();
+    let positions = poll_positions(base_consumer, 5, Duration::from_secs(30));
+    assert_eq!(positions, (0..5).map(|offset| (0, offset)).collect::<Vec<_>>());
+
+    consumer.pause_and_seek(&topic_name, 0, Offset::Offset(2), 5000).unwrap();
+    assert!(poll_positions(base_consumer, 1, Duration::from_secs(5)).is_empty());
 
+    let mut partitions = TopicPartitionList::new();
+    partitions.add_partition(&topic_name, 0);
+    consumer.resume(&partitions).unwrap();
+    let positions = poll_positions(base_consumer, 8, Duration::from_secs(30));
+    assert_eq!(positions, (2..10).map(|offset| (0, offset)).collect::<Vec<_>>());
+}
+
+// Committing the offset of a message should commit the following offset, only for its partition.
+#[test]
+fn test_consumer_commit_message_offset() {
+    let _r = env_logger::init();
+
+    let topic_name = rand_test_topic();
+    produce_messages(&topic_name, 10, &value_fn, &key_fn, Some(0), None);
+    produce_messages(&topic_name, 10, &value_fn, &key_fn, Some(1), None);
+    let consumer = create_stream_consumer(&rand_test_group(), None);
+    consumer.subscribe(&[topic_name.as_str()]).unwrap();
+
+    let _consumer_future = consumer.start()
+        .take(20)
+        .for_each(|message| {
+            match message {
+                Ok(m) => {
+                    if m.partition() == 0 && m.offset() == 4 {
+                        consumer.commit_message_offset(&m.detach(), CommitMode::Sync).unwrap();
+                    }
+                },
+                Err(e) => panic!("error receiving message: {:?}", e)
+            };
+            Ok(())
+        })
+        .wait();
+
+    let committed = consumer.committed(5000).unwrap();
+    assert_eq!(committed.find_partition(&topic_name, 0).unwrap().offset(), Offset::Offset(5));
+    assert_eq!(committed.find_partition(&topic_name, 1).unwrap().offset(), Offset::Invalid);
+}
+
+// The committed offsets of a group should be readable by a client outside of the group.
+#[test]
+fn test_committed_offsets_for_group() {
+    let _r = env_logger::init();
+
+    let topic_name = rand_test_topic();
+    produce_messages(&topic_name, 10, &value_fn, &key_fn, Some(0), None);
+    produce_messages(&topic_name, 10, &value_fn, &key_fn, Some(1), None);
+    let group_id = rand_test_group();
+
+    let consumer = create_stream_consumer(&group_id, None);
+    let mut offsets = TopicPartitionList::new();
+    offsets.add_partition_offset(&topic_name, 0, Offset::Offset(5));
+    consumer.assign(&offsets).unwrap();
+    consumer.commit(&offsets, CommitMode::Sync).unwrap();
+
+    let mut config = ClientConfig::new();
+    config.set("bootstrap.servers", get_bootstrap_server().as_str());
+    let mut partitions = TopicPartitionList::new();
+    partitions.add_partition(&topic_name, 0);
+    partitions.add_partition(&topic_name, 1);
+    let group_committed = committed_offsets_for_group(&config, &group_id, &partitions, 5000).unwrap();
+    assert_eq!(group_committed.find_partition(&topic_name, 0).unwrap().offset(), Offset::Offset(5));
+    assert_eq!(group_committed.find_partition(&topic_name, 1).unwrap().offset(), Offset::Invalid);
+}
+
+// The offsets of a group without active members should be committed without joining the group.
+#[test]
+fn test_commit_offsets_for_group() {
+    let _r = env_logger::init();
+
+    let topic_name = rand_test_topic();
+    produce_messages(&topic_name, 10, &value_fn, &key_fn, Some(0), None);
+    produce_messages(&topic_name, 10, &value_fn, &key_fn, Some(1), None);
+    let group_id = rand_test_group();
+
+    let mut config = ClientConfig::new();
+    config.set("bootstrap.servers", get_bootstrap_server().as_str());
+    let mut offsets = TopicPartitionList::new();
+    offsets.add_partition_offset(&topic_name, 0, Offset::Offset(0));
+    offsets.add_partition_offset(&topic_name, 1, Offset::Offset(3));
+    let results = commit_offsets_for_group(&config, &group_id, &offsets).unwrap();
+    assert!(results.elements().iter().all(|elem| elem.error().is_ok()));
+
+    // A member of the group should see the committed offsets.
+    let consumer = create_stream_consumer(&group_id, None);
+    let mut assignment = TopicPartitionList::new();
+    assignment.add_partition(&topic_name, 0);
+    assignment.add_partition(&topic_name, 1);
+    consumer.assign(&assignment).unwrap();
+    let committed = consumer.committed(5000).unwrap();
+    assert_eq!(committed.find_partition(&topic_name, 0).unwrap().offset(), Offset::Offset(0));
+    assert_eq!(committed.find_partition(&topic_name, 1).unwrap().offset(), Offset::Offset(3));
+}
 
 // TODO: add check that commit cb gets called correctly
 #[test]
@@ -200,6 +630,8 @@ fn test_consumer_commit_message() {
     assert_eq!(consumer.fetch_watermarks(&topic_name, 1, 5000).unwrap(), (0, 11));
     assert_eq!(consumer.fetch_watermarks(&topic_name, 2, 5000).unwrap(), (0, 12));
 
+    assert!(consumer.member_id().is_some());
+
     let mut assignment = TopicPartitionList::new();
     assignment.add_partition_offset(&topic_name, 0, Offset::Invalid);
     assignment.add_partition_offset(&topic_name, 1, Offset::Invalid);
@@ -217,6 +649,38 @@ fn test_consumer_commit_message() {
     position.add_partition_offset(&topic_name, 1, Offset::Offset(11));
     position.add_partition_offset(&topic_name, 2, Offset::Offset(12));
     assert_eq!(position, consumer.position().unwrap());
+    assert_eq!(position, consumer.effective_position(5000).unwrap());
+
+    let lag = consumer.fetch_lag(5000).unwrap();
+    assert_eq!(lag[&(topic_name.clone(), 0)], 0);
+    assert_eq!(lag[&(topic_name.clone(), 1)], 0);
+    assert_eq!(lag[&(topic_name.clone(), 2)], 0);
+}
+
+// Before any message is consumed, the lag should be computed from the committed offset, or from
+// the low watermark for partitions without a committed offset.
+#[test]
+fn test_consumer_fetch_lag_committed() {
+    let _r = env_logger::init();
+
+    let topic_name = rand_test_topic();
+    produce_messages(&topic_name, 10, &value_fn, &key_fn, Some(0), None);
+    produce_messages(&topic_name, 10, &value_fn, &key_fn, Some(1), None);
+    let consumer = create_stream_consumer(&rand_test_group(), None);
+
+    let mut offsets = TopicPartitionList::new();
+    offsets.add_partition_offset(&topic_name, 0, Offset::Offset(4));
+    consumer.assign(&offsets).unwrap();
+    consumer.commit(&offsets, CommitMode::Sync).unwrap();
+
+    let mut assignment = TopicPartitionList::new();
+    assignment.add_partition(&topic_name, 0);
+    assignment.add_partition(&topic_name, 1);
+    consumer.assign(&assignment).unwrap();
+
+    let lag = consumer.fetch_lag(5000).unwrap();
+    assert_eq!(lag[&(topic_name.clone(), 0)], 6);
+    assert_eq!(lag[&(topic_name.clone(), 1)], 10);
 }
 
 #[test]
@@ -231,6 +695,7 @@ fn test_consumer_store_offset_commit() {
     config.insert("enable.auto.offset.store", "false");
     config.insert("enable.partition.eof", "true");
     let consumer = create_stream_consumer(&rand_test_group(), Some(config));
+    consumer.enable_partition_eof(true);
     consumer.subscribe(&[topic_name.as_str()]).unwrap();
 
     let _consumer_future = consumer.start()
@@ -273,4 +738,57 @@ fn test_consumer_store_offset_commit() {
     position.add_partition_offset(&topic_name, 1, Offset::Offset(11));
     position.add_partition_offset(&topic_name, 2, Offset::Offset(12));
     assert_eq!(position, consumer.position().unwrap());
+    assert_eq!(position, consumer.effective_position(5000).unwrap());
+
+    // Offsets of partitions that are not known can't be stored.
+    let mut offsets = TopicPartitionList::new();
+    offsets.add_partition_offset(&topic_name, 0, Offset::Offset(5));
+    offsets.add_partition_offset(&topic_name, 100, Offset::Offset(5));
+    let results = consumer.store_offsets(&offsets).unwrap();
+    assert!(results.find_partition(&topic_name, 0).unwrap().error().is_ok());
+    match results.find_partition(&topic_name, 100).unwrap().error() {
+        Err(KafkaError::OffsetFetch(RDKafkaError::UnknownPartition)) => {},
+        result => panic!("Unexpected partition result: {:?}", result),
+    }
+
+    let mut offsets = TopicPartitionList::new();
+    offsets.add_partition_offset(&topic_name, 100, Offset::Offset(5));
+    match consumer.store_offsets(&offsets) {
+        Err(KafkaError::StoreOffset(RDKafkaError::UnknownPartition)) => {},
+        result => panic!("Unexpected store result: {:?}", result),
+    }
+}
+
+// For each partition, the offset following the highest one of the batch should be stored, even
+// when the messages are not in order.
+#[test]
+fn test_consumer_store_message_offsets() {
+    let _r = env_logger::init();
+
+    let topic_name = rand_test_topic();
+    produce_messages(&topic_name, 10, &value_fn, &key_fn, Some(0), None);
+    produce_messages(&topic_name, 10, &value_fn, &key_fn, Some(1), None);
+    let mut config = HashMap::new();
+    config.insert("enable.auto.offset.store", "false");
+    let consumer = create_stream_consumer(&rand_test_group(), Some(config));
+
+    let mut assignment = TopicPartitionList::new();
+    assignment.add_partition(&topic_name, 0);
+    assignment.add_partition(&topic_name, 1);
+    consumer.assign(&assignment).unwrap();
+
+    let messages = [(0, 3), (1, 9), (0, 7), (1, 2), (0, 5)].iter()
+        .map(|&(partition, offset)| {
+            OwnedMessage::new(None, None, topic_name.clone(), Timestamp::NotAvailable, partition, offset)
+        })
+        .collect::<Vec<_>>();
+    let results = consumer.store_message_offsets(&messages).unwrap();
+    assert_eq!(results.count(), 2);
+    assert_eq!(results.find_partition(&topic_name, 0).unwrap().offset(), Offset::Offset(8));
+    assert_eq!(results.find_partition(&topic_name, 1).unwrap().offset(), Offset::Offset(10));
+
+    consumer.commit_consumer_state(CommitMode::Sync).unwrap();
+    let committed = consumer.committed(5000).unwrap();
+    assert_eq!(committed.find_partition(&topic_name, 0).unwrap().offset(), Offset::Offset(8));
+    assert_eq!(committed.find_partition(&topic_name, 1).unwrap().offset(), Offset::Offset(10));
 }
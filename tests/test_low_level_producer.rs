@@ -0,0 +1,107 @@
+//! Test data production using the low level producer (BaseProducer).
+extern crate env_logger;
+extern crate futures;
+extern crate rand;
+extern crate rdkafka;
+
+use rdkafka::Message;
+use rdkafka::client::{Context, EmptyContext};
+use rdkafka::config::ClientConfig;
+use rdkafka::event::{Event, EventConsumer};
+use rdkafka::types::RDKafkaType;
+use rdkafka::producer::{BaseProducer, DeliveryResult, ProducerContext};
+
+mod utils;
+use utils::*;
+
+use std::sync::{Arc, Mutex};
+
+/// Topic configured with `message.timestamp.type=LogAppendTime`, created when the broker is
+/// started (see `docker/kafka_0.11.0.0/start-kafka.sh`).
+const LOG_APPEND_TIME_TOPIC: &'static str = "__test_log_append_time";
+
+#[derive(Clone)]
+struct TimestampContext {
+    timestamps: Arc<Mutex<Vec<(Option<i64>, bool)>>>,
+}
+
+impl Context for TimestampContext {}
+
+impl ProducerContext for TimestampContext {
+    type DeliveryContext = ();
+
+    fn delivery(&self, delivery_result: &DeliveryResult, _: Self::DeliveryContext) {
+        let message = delivery_result.as_ref().expect("Delivery failed");
+        let timestamp = (message.delivery_timestamp(), message.timestamp().is_log_append_time());
+        self.timestamps.lock().unwrap().push(timestamp);
+    }
+}
+
+fn create_timestamp_producer() -> (BaseProducer<TimestampContext>, TimestampContext) {
+    let context = TimestampContext { timestamps: Arc::new(Mutex::new(Vec::new())) };
+    let producer = ClientConfig::new()
+        .set("bootstrap.servers", get_bootstrap_server().as_str())
+        .set("api.version.request", "true")
+        .create_with_context::<_, BaseProducer<_>>(context.clone())
+        .expect("Producer creation error");
+    (producer, context)
+}
+
+// The delivery report should contain the timestamp the message was stored with. Topics created
+// automatically use `CreateTime`, so it should be the timestamp provided by the producer.
+#[test]
+fn test_produce_delivery_timestamp() {
+    let _r = env_logger::init();
+
+    let (producer, context) = create_timestamp_producer();
+    let topic_name = rand_test_topic();
+    producer.send_copy(&topic_name, None, Some("payload"), Some("key"), None, Some(1234)).unwrap();
+    producer.flush(10000);
+
+    assert_eq!(*context.timestamps.lock().unwrap(), vec![(Some(1234), false)]);
+}
+
+// If the topic uses `LogAppendTime`, the delivery report should contain the timestamp assigned by
+// the broker, and not the one provided by the producer.
+#[test]
+fn test_produce_delivery_timestamp_log_append_time() {
+    let _r = env_logger::init();
+
+    let (producer, context) = create_timestamp_producer();
+    producer.send_copy(LOG_APPEND_TIME_TOPIC, None, Some("payload"), Some("key"), None, Some(1234)).unwrap();
+    producer.flush(10000);
+
+    let timestamps = context.timestamps.lock().unwrap();
+    assert_eq!(timestamps.len(), 1);
+    let (timestamp, is_log_append_time) = timestamps[0];
+    assert!(is_log_append_time);
+    assert!(timestamp.is_some());
+    assert_ne!(timestamp, Some(1234));
+}
+
+// The delivery report should be returned as an event, instead of going through the context.
+#[test]
+fn test_event_consumer_delivery_report() {
+    let _r = env_logger::init();
+
+    let mut config = ClientConfig::new();
+    config.set("bootstrap.servers", get_bootstrap_server().as_str());
+    let producer = EventConsumer::new(&config, RDKafkaType::RD_KAFKA_PRODUCER, EmptyContext::new())
+        .expect("Producer creation error");
+
+    let topic_name = rand_test_topic();
+    producer.send_copy(&topic_name, Some(0), Some("payload"), Some("key"), None).unwrap();
+
+    let mut delivered = Vec::new();
+    for _ in 0..100 {
+        if let Some(Event::DeliveryReport(results)) = producer.poll_event(100) {
+            delivered.extend(results);
+            break;
+        }
+    }
+    assert_eq!(delivered.len(), 1);
+    let message = delivered[0].as_ref().expect("Delivery failed");
+    assert_eq!(message.topic(), topic_name.as_str());
+    assert_eq!(message.partition(), 0);
+    assert_eq!(message.payload_view::<str>(), Some(Ok("payload")));
+}
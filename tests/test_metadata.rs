@@ -33,15 +33,38 @@ fn test_metadata() {
 
     assert_eq!(ids, vec![0, 1, 2]);
     // assert_eq!(topic_metadata.error(), None);
+    assert!(topic_metadata.to_result().is_ok());
+    assert_eq!(consumer.client().partition_leader(&topic_name, 0, 5000), Some(0));
+    assert_eq!(consumer.client().partition_leader(&topic_name, 10, 5000), None);
     assert_eq!(topic_metadata.partitions().len(), 3);
     assert_eq!(topic_metadata.partitions()[0].leader(), 0);
     assert_eq!(topic_metadata.partitions()[1].leader(), 0);
     assert_eq!(topic_metadata.partitions()[2].leader(), 0);
     assert_eq!(topic_metadata.partitions()[0].replicas(), &[0]);
     assert_eq!(topic_metadata.partitions()[0].isr(), &[0]);
+    assert_eq!(topic_metadata.partitions().iter().filter(|p| p.leader() == 0).count(), 3);
+    assert_eq!(metadata.topics().iter().filter(|m| m.name() == topic_name).count(), 1);
+
+    let cluster_id = consumer.client().cluster_id(5000)
+        .expect("Metadata not received")
+        .expect("Cluster id not available");
+    assert!(!cluster_id.is_empty());
+
+    // The broker that served the metadata must be part of the cluster.
+    assert!(metadata.brokers().iter().any(|b| b.id() == metadata.orig_broker_id()));
+    assert!(!metadata.orig_broker_name().is_empty());
 
     let metadata_one_topic = consumer.fetch_metadata(Some(&topic_name), 5000).unwrap();
     assert_eq!(metadata_one_topic.topics().len(), 1);
+
+    let metadata_topics = consumer.fetch_metadata_for_topics(&[topic_name.as_str()], 5000).unwrap();
+    assert!(metadata_topics.topics().iter().any(|m| m.name() == topic_name));
+
+    assert!(consumer.topic_exists(&topic_name, 5000).unwrap());
+
+    let mut all_partitions = TopicPartitionList::new();
+    all_partitions.add_partition_range(&topic_name, 0, 2);
+    assert_eq!(consumer.all_partitions(&[topic_name.as_str()], 5000).unwrap(), all_partitions);
 }
 
 #[test]
@@ -58,6 +81,11 @@ fn test_subscription() {
     let mut tpl = TopicPartitionList::new();
     tpl.add_topic_unassigned(&topic_name);
     assert_eq!(tpl, consumer.subscription().unwrap());
+    assert!(consumer.subscription_patterns().unwrap().is_empty());
+
+    let pattern = format!("^{}.*", topic_name);
+    consumer.subscribe(&[pattern.as_str()]).unwrap();
+    assert_eq!(consumer.subscription_patterns().unwrap(), vec![pattern]);
 }
 
 #[test]
@@ -73,10 +101,8 @@ fn test_group_membership() {
     consumer.subscribe(&[topic_name.as_str()]).unwrap();
 
     // Make sure the consumer joins the group
-    let _consumer_future = consumer.start()
-        .take(1)
-        .for_each(|_| Ok(()))
-        .wait();
+    let assignment = consumer.wait_for_assignment(30000).unwrap();
+    assert!(assignment.count() > 0);
 
     let group_list = consumer.fetch_group_list(None, 5000).unwrap();
 
@@ -96,4 +122,7 @@ fn test_group_membership() {
 
     let consumer_member = &consumer_group.members()[0];
     assert_eq!(consumer_member.client_id(), "rdkafka_integration_test_client");
+
+    let group_list3 = consumer.fetch_group_list_async(Some(&group_name), 5000).wait().unwrap();
+    assert_eq!(group_list3.groups().len(), 1);
 }
@@ -34,7 +34,7 @@ pub fn rand_test_group() -> String {
     format!("__test_{}", id)
 }
 
-fn get_bootstrap_server() -> String {
+pub fn get_bootstrap_server() -> String {
     env::var("KAFKA_HOST").unwrap_or_else(|_| "localhost:9092".to_owned())
 }
 